@@ -0,0 +1,147 @@
+#![cfg(feature = "put")]
+use std::io::ErrorKind;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use crate::auth::BasicAuthentication;
+use crate::put::sibling_path;
+use crate::status::Return;
+
+/// Directory next to the repository configs, which holds one `<repo>.jsonl` audit log per repository.
+/// It's outside the repositories, so that the logs are never served.
+const AUDIT_DIR: &str = ".audit";
+/// Log for rejected requests, which don't name an existing repository.
+pub const UNKNOWN_REPOSITORY: &str = ".unknown";
+
+/// Who made a change to a repository.
+#[derive(Debug, Clone, Default)]
+pub struct Actor {
+    /// Name of the token, which authenticated the request. None, as long as the request is unauthenticated.
+    pub token: Option<String>,
+    pub client_ip: Option<IpAddr>,
+}
+impl Actor {
+    /// An actor, which hasn't authenticated (yet).
+    pub fn new(client_ip: Option<IpAddr>) -> Self {
+        Self {
+            token: None,
+            client_ip,
+        }
+    }
+
+    /// Records the token of `auth` as authenticated, if `result` of [`crate::repository::Repository::check_auth`] shows, that its password was verified.
+    /// Forbidden requests are only rejected after that, whilst unauthorized ones may have failed before.
+    pub fn authenticated(&mut self, auth: Option<&BasicAuthentication>, result: &Result<bool, Return>) {
+        let verified = match result {
+            Ok(v) => *v,
+            Err(err) => err.status == actix_web::http::StatusCode::FORBIDDEN,
+        };
+        if verified && let Some(auth) = auth {
+            self.token = Some(auth.username.clone());
+        }
+    }
+}
+
+/// Size and checksums of a file, as far as they are known.
+#[derive(Debug, Clone, Default)]
+pub struct FileSummary {
+    pub size: Option<u64>,
+    pub sha1: Option<String>,
+    pub sha256: Option<String>,
+}
+impl FileSummary {
+    /// Reads the size and the stored checksums of a deployed file.
+    /// Directories and missing files have an empty summary.
+    pub async fn of(file_path: &Path) -> Self {
+        let size = match tokio::fs::metadata(file_path).await {
+            Ok(v) if v.is_file() => v.len(),
+            _ => return Self::default(),
+        };
+        Self {
+            size: Some(size),
+            sha1: read_checksum(file_path, "sha1").await,
+            sha256: read_checksum(file_path, "sha256").await,
+        }
+    }
+}
+
+async fn read_checksum(file_path: &Path, extension: &str) -> Option<String> {
+    match tokio::fs::read_to_string(sibling_path(file_path, extension)).await {
+        Ok(v) => v.split_whitespace().next().map(str::to_owned),
+        Err(_) => None,
+    }
+}
+
+#[derive(Debug, serde_derive::Serialize)]
+struct AuditRecord<'a> {
+    timestamp: chrono::DateTime<chrono::Utc>,
+    action: &'a str,
+    token: Option<&'a str>,
+    authenticated: bool,
+    client_ip: Option<IpAddr>,
+    path: &'a str,
+    size: Option<u64>,
+    sha256: Option<&'a str>,
+    success: bool,
+    status: u16,
+}
+
+/// Appends a record of a change to the audit log of the repository.
+pub async fn record(repo: &str, actor: &Actor, action: &str, path: &Path, summary: &FileSummary, status: actix_web::http::StatusCode) {
+    let path = path.to_string_lossy();
+    let record = AuditRecord {
+        timestamp: chrono::Utc::now(),
+        action,
+        token: actor.token.as_deref(),
+        authenticated: actor.token.is_some(),
+        client_ip: actor.client_ip,
+        path: &path,
+        size: summary.size,
+        sha256: summary.sha256.as_deref(),
+        success: status.is_success(),
+        status: status.as_u16(),
+    };
+    append_record(AUDIT_DIR, repo, &record).await;
+}
+
+/// Appends `record` as a line to the `<repo>.jsonl` file in `dir`.
+/// Failing to write the record is logged, but doesn't fail the request.
+pub async fn append_record<T: serde::Serialize + std::fmt::Debug>(dir: &str, repo: &str, record: &T) {
+    let mut line = match serde_json::to_vec(record) {
+        Ok(v) => v,
+        Err(err) => {
+            tracing::error!("{repo}: Failed to serialize record {record:?}: {err}");
+            return;
+        }
+    };
+    line.push(b'\n');
+
+    let log_path = PathBuf::from(dir).join(format!("{repo}.jsonl"));
+    let mut file = match open_log(dir, &log_path).await {
+        Ok(v) => v,
+        Err(err) => {
+            tracing::error!("{repo}: Failed to open log {}: {err}. Record: {record:?}", log_path.display());
+            return;
+        }
+    };
+    //The whole line is written at once, so that concurrent appends don't interleave.
+    let result = match file.write_all(&line).await {
+        Ok(()) => file.flush().await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        tracing::error!("{repo}: Failed to write to log {}: {err}. Record: {record:?}", log_path.display());
+    }
+}
+
+async fn open_log(dir: &str, log_path: &Path) -> Result<tokio::fs::File, std::io::Error> {
+    let mut options = tokio::fs::OpenOptions::new();
+    options.create(true).append(true);
+    match options.open(log_path).await {
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            tokio::fs::create_dir_all(dir).await?;
+            options.open(log_path).await
+        },
+        other => other,
+    }
+}
@@ -1,7 +1,7 @@
 use std::time::Instant;
 use crate::status::{Content, Return};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BasicAuthentication {
     pub username: String,
     pub password: String,
@@ -0,0 +1,821 @@
+#![cfg(feature = "put")]
+use std::collections::{HashMap, HashSet};
+use std::io::{ErrorKind, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use futures::TryStreamExt;
+use tokio::io::AsyncReadExt;
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+use crate::audit::{Actor, FileSummary};
+use crate::err::GetRepoFileError;
+use crate::checksum_deploy::StoredDigests;
+use crate::path_info::{lock_batch_metadata, metadata_update_failed, split_sibling_extensions, BatchMetadata, MetadataChange, NonUniqueSnapshot, PathInfo, CHECKSUM_EXTENSIONS, SIGNATURE_EXTENSION};
+use crate::put::{new_snapshot_value, replace_metadata_file, sibling_path, stage_file, unique_snapshot_path, StagedFile};
+use crate::repository::{RedeployPolicy, Repository};
+use crate::request_digest::RequestDigests;
+use crate::status::{Content, Return};
+use crate::temp_file;
+use crate::webhook::{ChangedFile, EventKind};
+
+/// Maximum number of files, which get extracted from a bundle.
+const MAX_BUNDLE_ENTRIES: usize = 10_000;
+
+/// A file, which gets deployed as part of a bundle.
+pub struct BundleEntry {
+    /// Path relative to the repository
+    pub path: PathBuf,
+    /// File with the content to deploy, e.g. the temporary file an archive entry was extracted to.
+    pub source: PathBuf,
+}
+
+/// Errors while extracting an archive, which only become a [`Return`] once extracting is done.
+enum ExtractError {
+    Repo(GetRepoFileError),
+    /// An error caused by a specific entry of the archive
+    Entry(PathBuf, GetRepoFileError),
+    /// The archive is malformed, with the reason
+    BadArchive(String),
+}
+impl ExtractError {
+    fn into_return(self) -> Return {
+        match self {
+            Self::Repo(err) => err.to_return(),
+            Self::Entry(path, err) => entry_error(&path, err.to_return()),
+            Self::BadArchive(message) => Return {
+                status: actix_web::http::StatusCode::BAD_REQUEST,
+                content: Content::String(message),
+                content_type: actix_web::http::header::ContentType::plaintext(),
+                header_map: None,
+            },
+        }
+    }
+}
+
+/// Deploys a tar, tar.gz or zip archive laid out like a maven repository into `prefix` of the repository.
+/// Every file goes through the same checks and maven-metadata updates as a single PUT, and either all or none of them get deployed.
+/// Checksums and maven-metadata files in the archive are ignored, because the server generates those.
+pub async fn deploy_bundle(repo: Arc<str>, config: &Arc<Repository>, actor: &Actor, prefix: PathBuf, data: actix_web::web::Payload) -> Return {
+    let max_file_size = config.max_file_size.unwrap_or(crate::DEFAULT_MAX_FILE_SIZE);
+    let repo_dir = PathBuf::from(&*repo);
+    let archive_path = temp_file::temp_path(&repo_dir.join("bundle"));
+    let mut archive = match tokio::fs::File::create_new(&archive_path).await {
+        Ok(v) => v,
+        Err(err) => {
+            tracing::error!("Failed to create temporary file {} for a bundle: {err}", archive_path.display());
+            return GetRepoFileError::FileCreateFailed.to_return();
+        }
+    };
+    let mut data = data.map_err(std::io::Error::other).into_async_read().compat().take(max_file_size + 1);
+    let received = tokio::io::copy(&mut data, &mut archive).await;
+    drop(archive);
+    match received {
+        Ok(v) if v > max_file_size => {
+            temp_file::remove(&archive_path).await;
+            return GetRepoFileError::PutFileTooLarge.to_return();
+        },
+        Ok(_) => {},
+        Err(err) => {
+            tracing::error!("Failed to receive bundle into {}: {err}", archive_path.display());
+            temp_file::remove(&archive_path).await;
+            return GetRepoFileError::FileWriteFailed.to_return();
+        }
+    }
+
+    let entries = {
+        let archive_path = archive_path.clone();
+        tokio::task::spawn_blocking(move ||extract(&archive_path, &repo_dir, &prefix, max_file_size)).await
+    };
+    temp_file::remove(&archive_path).await;
+    let entries = match entries {
+        Ok(Ok(v)) => v,
+        Ok(Err(err)) => return err.into_return(),
+        Err(err) => {
+            tracing::error!("Panicked whilst extracting bundle: {err}");
+            return GetRepoFileError::Panicked.to_return();
+        }
+    };
+
+    let result = deploy_entries(&repo, config, actor, &entries).await;
+    for entry in &entries {
+        temp_file::remove(&entry.source).await;
+    }
+    match result {
+        Ok(paths) => {
+            let mut out = String::new();
+            for path in paths {
+                out.push_str(&path.to_string_lossy());
+                out.push('\n');
+            }
+            Return {
+                status: actix_web::http::StatusCode::CREATED,
+                content: Content::String(out),
+                content_type: actix_web::http::header::ContentType::plaintext(),
+                header_map: None,
+            }
+        },
+        Err(err) => err,
+    }
+}
+
+/// Lists the files below `path` (relative to `repo_dir`), which need to be deployed to recreate it in another repository.
+/// `path` may also point to a single file.
+/// Checksums and maven-metadata get generated by the deploy, and hidden files are internal.
+pub fn collect_files(repo_dir: &Path, path: &Path) -> Result<Vec<BundleEntry>, std::io::Error> {
+    let mut entries = Vec::new();
+    if std::fs::metadata(repo_dir.join(path))?.is_file() {
+        if path.file_name().and_then(|v|v.to_str()).is_some_and(is_deployable) {
+            entries.push(BundleEntry {
+                source: repo_dir.join(path),
+                path: path.to_path_buf(),
+            });
+        }
+        return Ok(entries);
+    }
+    let mut to_visit = vec![path.to_path_buf()];
+    while let Some(dir) = to_visit.pop() {
+        for entry in std::fs::read_dir(repo_dir.join(&dir))? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = match name.to_str() {
+                Some(v) => v,
+                None => continue,
+            };
+            if name.starts_with(".") {
+                continue;
+            }
+            let path = dir.join(name);
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                to_visit.push(path);
+            } else if file_type.is_file() && is_deployable(name) {
+                entries.push(BundleEntry {
+                    source: entry.path(),
+                    path,
+                });
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// The server generates checksums and the maven-metadata itself, and files starting with a '.' are used internally (e.g. for the FileMetadata).
+pub fn is_deployable(file_name: &str) -> bool {
+    !file_name.starts_with(".")
+        && !file_name.starts_with("maven-metadata.xml")
+        && !Path::new(file_name).extension().and_then(|v|v.to_str()).is_some_and(|v|CHECKSUM_EXTENSIONS.contains(&v))
+}
+
+/// A file of the bundle, which passed all checks and gets stored at `path`.
+struct PlannedEntry<'a> {
+    entry: &'a BundleEntry,
+    /// Path relative to the repository, with non-unique snapshots resolved to a timestamped build
+    path: PathBuf,
+    /// The file already exists and gets replaced
+    existed: bool,
+    /// The file belongs to a snapshot build
+    snapshot: bool,
+    redeploy_policy: RedeployPolicy,
+}
+
+/// Deploys all entries or none of them.
+/// The affected maven-metadata files are locked first, so that the builds of non-unique snapshots are assigned, while no other deploy can add one.
+/// All files are written to temporary files. Once that succeeded, they are moved to their final locations,
+/// and every affected maven-metadata file gets updated once, before the locks are released.
+/// If anything fails, the files, maven-metadata and directories are restored to their previous state.
+/// Returns the paths the entries were stored under.
+pub async fn deploy_entries(repo: &Arc<str>, config: &Arc<Repository>, actor: &Actor, entries: &[BundleEntry]) -> Result<Vec<PathBuf>, Return> {
+    let max_file_size = config.max_file_size.unwrap_or(crate::DEFAULT_MAX_FILE_SIZE);
+    //Which maven-metadata files are affected doesn't depend on the builds.
+    let unlocked_paths = resolve_snapshot_paths(repo, entries, None).await?;
+    let mut created_dirs = Vec::new();
+    for path in &unlocked_paths {
+        let file_path = Path::new(&**repo).join(path);
+        if let Err(err) = create_parent_dirs(&file_path, &mut created_dirs).await {
+            tracing::error!("Failed to create dirs while deploying bundle entry {}: {err}", path.display());
+            remove_created_dirs(&created_dirs).await;
+            return Err(GetRepoFileError::FileCreateFailed.to_return());
+        }
+    }
+    let mut locked = match lock_batch_metadata(repo, &metadata_infos(&unlocked_paths)).await {
+        Ok(v) => v,
+        Err(err) => {
+            remove_created_dirs(&created_dirs).await;
+            return Err(err);
+        }
+    };
+    let created_metadata = locked.created_files().map(Path::to_path_buf).collect::<Vec<_>>();
+    let paths = match resolve_snapshot_paths(repo, entries, Some(&locked)).await {
+        Ok(v) => v,
+        Err(err) => {
+            remove_created(&created_metadata, &created_dirs).await;
+            return Err(err);
+        }
+    };
+    let metadata = match locked.update(repo, &metadata_infos(&paths)) {
+        Ok(v) => v,
+        Err(message) => {
+            remove_created(&created_metadata, &created_dirs).await;
+            return Err(metadata_update_failed(message));
+        }
+    };
+
+    //Fail before any file is written, if possible.
+    let mut planned = Vec::with_capacity(entries.len());
+    for (entry, path) in entries.iter().zip(&paths) {
+        match check_entry(repo, config, entry, path.clone()).await {
+            Ok(Some(v)) => planned.push(v),
+            //An identical signature is already stored.
+            Ok(None) => {},
+            Err(err) => {
+                crate::audit::record(repo, actor, "put", &entry.path, &FileSummary::default(), err.status).await;
+                remove_created(&created_metadata, &created_dirs).await;
+                return Err(entry_error(&entry.path, err));
+            }
+        }
+    }
+
+    let mut staged = Vec::with_capacity(planned.len());
+    for planned in &planned {
+        match stage_entry(repo, planned, max_file_size, &mut created_dirs).await {
+            Ok(v) => staged.push(v),
+            Err(err) => {
+                tracing::warn!("Rolling back bundle, because {} failed to deploy", planned.entry.path.display());
+                crate::audit::record(repo, actor, "put", &planned.entry.path, &FileSummary::default(), err.status).await;
+                for staged in &staged {
+                    staged.discard().await;
+                }
+                remove_created(&created_metadata, &created_dirs).await;
+                return Err(entry_error(&planned.entry.path, err));
+            }
+        }
+    }
+
+    //Files, which get overwritten, are kept as hard-links, so that they can be restored.
+    let mut backups = Vec::new();
+    for planned in planned.iter().filter(|v|v.existed) {
+        let file_path = Path::new(&**repo).join(&planned.path);
+        let mut files = vec![file_path.clone()];
+        files.extend(CHECKSUM_EXTENSIONS.iter().map(|v|sibling_path(&file_path, v)));
+        for file in files {
+            let backup = temp_file::temp_path(&file);
+            match tokio::fs::hard_link(&file, &backup).await {
+                Ok(()) => backups.push((file, backup)),
+                Err(err) if err.kind() == ErrorKind::NotFound => {},
+                Err(err) => {
+                    tracing::error!("Failed to back up {} before deploying a bundle: {err}", file.display());
+                    remove_backups(&backups).await;
+                    for staged in &staged {
+                        staged.discard().await;
+                    }
+                    remove_created(&created_metadata, &created_dirs).await;
+                    return Err(GetRepoFileError::FileWriteFailed.to_return());
+                }
+            }
+        }
+    }
+
+    let mut deployed = Vec::with_capacity(planned.len());
+    let mut staged = staged.into_iter();
+    let mut result = Ok(());
+    for (planned, file) in planned.iter().zip(&mut staged) {
+        match file.persist(planned.redeploy_policy).await {
+            Ok(digests) => deployed.push((planned, digests)),
+            Err(err) => {
+                crate::audit::record(repo, actor, "put", &planned.entry.path, &FileSummary::default(), err.status).await;
+                result = Err(entry_error(&planned.entry.path, err));
+                break;
+            }
+        }
+    }
+    let mut written = 0;
+    if result.is_ok() {
+        for change in &metadata {
+            match replace_metadata_file(change.path.clone(), change.contents.clone(), max_file_size).await {
+                Ok(()) => written += 1,
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
+        }
+    }
+    if let Err(err) = result {
+        tracing::warn!("Rolling back bundle, because it failed to deploy: {} {:?}", err.status, err.content);
+        for file in staged {
+            file.discard().await;
+        }
+        rollback(repo, &deployed, &backups, &metadata, written, max_file_size).await;
+        drop(locked);
+        remove_backups(&backups).await;
+        remove_created_dirs(&created_dirs).await;
+        return Err(err);
+    }
+    drop(locked);
+    remove_backups(&backups).await;
+
+    let mut version_dirs = HashSet::new();
+    let mut changed = Vec::with_capacity(deployed.len());
+    for (planned, digests) in deployed {
+        let file_path = Path::new(&**repo).join(&planned.path);
+        crate::checksum_deploy::index_file(file_path.clone(), digests);
+        let summary = FileSummary::of(&file_path).await;
+        crate::audit::record(repo, actor, "put", &planned.path, &summary, actix_web::http::StatusCode::CREATED).await;
+        crate::events::publish(repo, crate::events::ChangeKind::Deployed, &planned.path);
+        changed.push(ChangedFile {
+            path: planned.path.clone(),
+            summary,
+        });
+        if planned.snapshot && let Some(version_dir) = planned.path.parent() {
+            version_dirs.insert(version_dir.to_path_buf());
+        }
+    }
+    crate::webhook::notify(repo, config, EventKind::Deploy, &changed);
+    //Only pruned once everything is deployed, so that a rollback never races with it.
+    if config.snapshot_retention.is_some() {
+        for version_dir in version_dirs {
+            crate::snapshot_retention::schedule_prune(repo.clone(), config.clone(), version_dir);
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Parses the paths of the files, which show up in the maven-metadata.
+fn metadata_infos(paths: &[PathBuf]) -> Vec<PathInfo<'_>> {
+    paths.iter()
+        .filter(|v|v.extension().is_none_or(|v|v != SIGNATURE_EXTENSION))
+        .filter_map(|v|PathInfo::parse(v).ok())
+        .collect()
+}
+
+/// Resolves the paths of non-unique snapshot files (e.g. `foo-1.0-SNAPSHOT.jar`) to timestamped ones, like a single PUT does.
+/// All non-unique files of a version in the bundle are added to one new build, and their signatures are attached to it.
+/// Other signatures are attached to the newest build, which contains the file they belong to.
+/// The new builds follow the builds in the `locked` maven-metadata. Without it, they only locate the maven-metadata.
+async fn resolve_snapshot_paths(repo: &str, entries: &[BundleEntry], locked: Option<&BatchMetadata>) -> Result<Vec<PathBuf>, Return> {
+    let mut builds = HashMap::<PathBuf, String>::new();
+    let mut artifacts = HashSet::new();
+    let mut out = Vec::with_capacity(entries.len());
+    //Signatures are resolved afterwards, because they might be listed before the file they belong to.
+    for sibling_pass in [false, true] {
+        for (i, entry) in entries.iter().enumerate() {
+            let file_name = match entry.path.file_name().and_then(|v|v.to_str()) {
+                Some(v) => v,
+                None => continue,
+            };
+            let (artifact_name, sibling_extensions) = split_sibling_extensions(file_name);
+            if sibling_extensions.is_empty() == sibling_pass {
+                continue;
+            }
+            let artifact_path = entry.path.with_file_name(artifact_name);
+            let info = match NonUniqueSnapshot::parse(&artifact_path) {
+                Some(v) => v,
+                None => {
+                    out.push((i, entry.path.clone()));
+                    continue;
+                }
+            };
+            let version_dir = entry.path.parent().map(Path::to_path_buf).unwrap_or_default();
+            let value = match builds.get(&version_dir) {
+                Some(value) if !sibling_pass || artifacts.contains(&artifact_path) => value.clone(),
+                _ if sibling_pass => {
+                    let path = unique_snapshot_path(repo, entry.path.clone()).await.map_err(|err|entry_error(&entry.path, err))?;
+                    out.push((i, path));
+                    continue;
+                },
+                _ => {
+                    let value = new_snapshot_value(info.version, locked.and_then(|v|v.snapshot_versioning(repo, &entry.path)));
+                    builds.insert(version_dir, value.clone());
+                    value
+                },
+            };
+            let mut unique_name = info.unique_file_name(&value);
+            unique_name.push_str(sibling_extensions);
+            out.push((i, entry.path.with_file_name(unique_name)));
+            artifacts.insert(artifact_path);
+        }
+    }
+    out.sort_by_key(|(i, _)|*i);
+    Ok(out.into_iter().map(|(_, path)|path).collect())
+}
+
+/// Checks, that the entry can be deployed to `path`, without writing anything.
+/// Returns `None`, if the entry is a signature, which is already stored with the same content.
+async fn check_entry<'a>(repo: &str, config: &Repository, entry: &'a BundleEntry, path: PathBuf) -> Result<Option<PlannedEntry<'a>>, Return> {
+    let file_path = Path::new(repo).join(&path);
+    let is_signature = path.extension().is_some_and(|v|v == SIGNATURE_EXTENSION);
+    let info = if is_signature {
+        None
+    } else {
+        Some(PathInfo::parse(&path)?)
+    };
+    if let Some(info) = &info && info.extension == Some("pom") {
+        let pom = match tokio::fs::read(&entry.source).await {
+            Ok(v) => v,
+            Err(err) => {
+                tracing::error!("Failed to read bundle entry {}: {err}", entry.source.display());
+                return Err(GetRepoFileError::OpenFile.to_return());
+            }
+        };
+        if let Err(message) = crate::pom::validate(&pom, info) {
+            return Err(Return {
+                status: actix_web::http::StatusCode::BAD_REQUEST,
+                content: Content::String(message),
+                content_type: actix_web::http::header::ContentType::plaintext(),
+                header_map: None,
+            });
+        }
+    }
+    let snapshot = info.as_ref().is_some_and(|v|v.snapshot.is_some());
+    let redeploy_policy = if snapshot {
+        RedeployPolicy::Overwrite
+    } else if is_signature {
+        RedeployPolicy::IdenticalOnly
+    } else {
+        config.redeploy_policy.unwrap_or_default()
+    };
+    let existed = match tokio::fs::try_exists(&file_path).await {
+        Ok(v) => v,
+        Err(err) => {
+            tracing::error!("Failed to check if {} exists: {err}", file_path.display());
+            return Err(GetRepoFileError::OpenFile.to_return());
+        }
+    };
+    if existed && is_signature {
+        //Like a single PUT, a re-uploaded signature is only compared to the stored one.
+        return match (tokio::fs::read(&file_path).await, tokio::fs::read(&entry.source).await) {
+            (Ok(stored), Ok(uploaded)) if stored == uploaded => Ok(None),
+            (Ok(_), Ok(_)) => Err(Return {
+                status: actix_web::http::StatusCode::BAD_REQUEST,
+                content: Content::Str("The uploaded signature doesn't match the stored one"),
+                content_type: actix_web::http::header::ContentType::plaintext(),
+                header_map: None,
+            }),
+            (Err(err), _) | (_, Err(err)) => {
+                tracing::error!("Failed to compare signature {} with {}: {err}", file_path.display(), entry.source.display());
+                Err(GetRepoFileError::OpenFile.to_return())
+            }
+        };
+    }
+    if existed && redeploy_policy == RedeployPolicy::Immutable {
+        return Err(Return {
+            status: actix_web::http::StatusCode::CONFLICT,
+            content: Content::Str("File already exists"),
+            content_type: actix_web::http::header::ContentType::plaintext(),
+            header_map: None,
+        });
+    }
+    Ok(Some(PlannedEntry {
+        entry,
+        path,
+        existed,
+        snapshot,
+        redeploy_policy,
+    }))
+}
+
+/// Copies the entry into a temporary file next to its final location and generates its checksum files.
+async fn stage_entry(repo: &str, planned: &PlannedEntry<'_>, limit: u64, created_dirs: &mut Vec<PathBuf>) -> Result<StagedFile, Return> {
+    let file_path = Path::new(repo).join(&planned.path);
+    if let Err(err) = create_parent_dirs(&file_path, created_dirs).await {
+        tracing::error!("Failed to create dirs while deploying bundle entry {}: {err}", planned.path.display());
+        return Err(GetRepoFileError::FileCreateFailed.to_return());
+    }
+    let source = match tokio::fs::File::open(&planned.entry.source).await {
+        Ok(v) => v,
+        Err(err) => {
+            tracing::error!("Failed to open bundle entry {}: {err}", planned.entry.source.display());
+            return Err(GetRepoFileError::OpenFile.to_return());
+        }
+    };
+    let temp_path = temp_file::temp_path(&file_path);
+    let file = match tokio::fs::File::create_new(&temp_path).await {
+        Ok(v) => v,
+        Err(err) => {
+            tracing::error!("Failed to create temporary file {}: {err}", temp_path.display());
+            return Err(GetRepoFileError::FileCreateFailed.to_return());
+        }
+    };
+    stage_file(file, file_path, temp_path, &RequestDigests::default(), limit, source).await
+}
+
+/// Creates the missing parent directories of `file_path` and remembers them, so that a rollback can remove them again.
+async fn create_parent_dirs(file_path: &Path, created_dirs: &mut Vec<PathBuf>) -> Result<(), std::io::Error> {
+    let mut missing = Vec::new();
+    let mut dir = file_path.parent();
+    while let Some(current) = dir && !current.as_os_str().is_empty() {
+        if tokio::fs::try_exists(current).await? {
+            break;
+        }
+        missing.push(current.to_path_buf());
+        dir = current.parent();
+    }
+    for dir in missing.into_iter().rev() {
+        match tokio::fs::create_dir(&dir).await {
+            Ok(()) => created_dirs.push(dir),
+            //Created by a concurrent deploy
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {},
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+/// Undoes locking the maven-metadata before anything was deployed: Removes the maven-metadata files, which were created by locking them, and the created directories.
+/// The maven-metadata needs to still be locked.
+async fn remove_created(created_metadata: &[PathBuf], created_dirs: &[PathBuf]) {
+    for path in created_metadata {
+        remove_with_checksums(path).await;
+    }
+    remove_created_dirs(created_dirs).await;
+}
+
+/// Removes the directories created for the bundle, deepest first.
+/// Directories, which a concurrent deploy wrote to, are kept.
+async fn remove_created_dirs(created_dirs: &[PathBuf]) {
+    for dir in created_dirs.iter().rev() {
+        if let Err(err) = tokio::fs::remove_dir(dir).await {
+            tracing::info!("Keeping directory {} created for a bundle, which was rolled back: {err}", dir.display());
+        }
+    }
+}
+
+/// Removes the newly deployed files, restores overwritten files and the maven-metadata.
+/// The first `written` maven-metadata files were already replaced.
+async fn rollback(repo: &str, deployed: &[(&PlannedEntry<'_>, StoredDigests)], backups: &[(PathBuf, PathBuf)], metadata: &[MetadataChange], written: usize, limit: u64) {
+    for (planned, _) in deployed.iter().rev() {
+        if !planned.existed {
+            remove_with_checksums(&Path::new(repo).join(&planned.path)).await;
+        }
+    }
+    for (file, backup) in backups {
+        if let Err(err) = temp_file::persist(backup, file, true).await {
+            tracing::error!("Failed to restore {} from {}: {err}", file.display(), backup.display());
+        }
+    }
+    for (i, change) in metadata.iter().enumerate() {
+        if change.previous.is_empty() {
+            //Created while locking it
+            remove_with_checksums(&change.path).await;
+        } else if i < written
+            && let Err(err) = replace_metadata_file(change.path.clone(), change.previous.clone(), limit).await {
+            tracing::error!("Failed to restore maven-metadata {}: {} {:?}", change.path.display(), err.status, err.content);
+        }
+    }
+}
+
+/// Removes a file, its checksum files and their [`FileMetadata`](crate::file_metadata::FileMetadata).
+async fn remove_with_checksums(file_path: &Path) {
+    let files = core::iter::once(file_path.to_path_buf())
+        .chain(CHECKSUM_EXTENSIONS.iter().map(|v|sibling_path(file_path, v)))
+        .collect::<Vec<_>>();
+    for file in files {
+        let metadata_path = crate::file_metadata::FileMetadata::file_path_to_metadata_path(&file).ok();
+        for path in core::iter::once(file.as_path()).chain(metadata_path.as_deref()) {
+            match tokio::fs::remove_file(path).await {
+                Ok(()) => {},
+                Err(err) if err.kind() == ErrorKind::NotFound => {},
+                Err(err) => tracing::error!("Failed to roll back {}: {err}", path.display()),
+            }
+        }
+    }
+}
+async fn remove_backups(backups: &[(PathBuf, PathBuf)]) {
+    for (_, backup) in backups {
+        temp_file::remove(backup).await;
+    }
+}
+
+/// Prefixes the error message with the path of the entry, which caused it.
+fn entry_error(path: &Path, err: Return) -> Return {
+    let message = match &err.content {
+        Content::Str(v) => v,
+        Content::String(v) => v.as_str(),
+        _ => "",
+    };
+    Return {
+        status: err.status,
+        content: Content::String(format!("{}: {message}", path.display())),
+        content_type: actix_web::http::header::ContentType::plaintext(),
+        header_map: err.header_map,
+    }
+}
+
+/// Extracts all files of the archive to temporary files in the root of the repository.
+/// Together, the extracted files may be at most `limit` bytes large.
+/// On error, all extracted files are removed again.
+fn extract(archive_path: &Path, repo_dir: &Path, prefix: &Path, limit: u64) -> Result<Vec<BundleEntry>, ExtractError> {
+    let mut entries = Vec::new();
+    match extract_archive(archive_path, repo_dir, prefix, limit, &mut entries) {
+        Ok(()) => Ok(entries),
+        Err(err) => {
+            for entry in entries {
+                if let Err(err) = std::fs::remove_file(&entry.source) && err.kind() != ErrorKind::NotFound {
+                    tracing::error!("Error deleting temporary File {}: {err}", entry.source.display());
+                }
+            }
+            Err(err)
+        }
+    }
+}
+
+fn extract_archive(archive_path: &Path, repo_dir: &Path, prefix: &Path, limit: u64, entries: &mut Vec<BundleEntry>) -> Result<(), ExtractError> {
+    let mut file = std::fs::File::open(archive_path).map_err(|err|{
+        tracing::error!("Failed to open received bundle {}: {err}", archive_path.display());
+        ExtractError::Repo(GetRepoFileError::OpenFile)
+    })?;
+    let mut magic = [0; 4];
+    let read = file.read(&mut magic).map_err(|err|{
+        tracing::error!("Failed to read received bundle {}: {err}", archive_path.display());
+        ExtractError::Repo(GetRepoFileError::OpenFile)
+    })?;
+    drop(file);
+    let file = std::fs::File::open(archive_path).map_err(|err|{
+        tracing::error!("Failed to open received bundle {}: {err}", archive_path.display());
+        ExtractError::Repo(GetRepoFileError::OpenFile)
+    })?;
+    let mut seen = HashSet::new();
+    //Archives are compressed, so the extracted files are limited in total as well.
+    let mut remaining = limit;
+
+    if magic[..read].starts_with(b"PK\x03\x04") {
+        let mut archive = zip::ZipArchive::new(file).map_err(|err|bad_archive(&err))?;
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i).map_err(|err|bad_archive(&err))?;
+            if entry.is_dir() {
+                continue;
+            }
+            let name = match entry.enclosed_name() {
+                Some(v) => v,
+                None => return Err(ExtractError::Repo(GetRepoFileError::BadRequestPath)),
+            };
+            extract_entry(repo_dir, prefix, &name, &mut entry, &mut remaining, &mut seen, entries)?;
+        }
+        return Ok(());
+    }
+
+    let reader: Box<dyn Read> = if magic[..read].starts_with(&[0x1f, 0x8b]) {
+        Box::new(flate2::read::MultiGzDecoder::new(std::io::BufReader::new(file)))
+    } else {
+        Box::new(std::io::BufReader::new(file))
+    };
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries().map_err(|err|bad_archive(&err))? {
+        let mut entry = entry.map_err(|err|bad_archive(&err))?;
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_dir() || entry_type.is_pax_global_extensions() || entry_type.is_pax_local_extensions() {
+            continue;
+        }
+        let name = entry.path().map_err(|err|bad_archive(&err))?.into_owned();
+        if !entry_type.is_file() {
+            return Err(ExtractError::BadArchive(format!("{}: Only regular files are supported in bundles", name.display())));
+        }
+        extract_entry(repo_dir, prefix, &name, &mut entry, &mut remaining, &mut seen, entries)?;
+    }
+    Ok(())
+}
+
+/// Extracts a single file of the archive, if it gets deployed.
+/// `remaining` is the size, which all further files of the archive may have in total.
+fn extract_entry(repo_dir: &Path, prefix: &Path, name: &Path, data: &mut dyn Read, remaining: &mut u64, seen: &mut HashSet<PathBuf>, entries: &mut Vec<BundleEntry>) -> Result<(), ExtractError> {
+    let mut relative = PathBuf::new();
+    for component in name.components() {
+        match component {
+            Component::CurDir => continue,
+            Component::Normal(v) => {
+                let v = match v.to_str() {
+                    Some(v) => v,
+                    None => return Err(ExtractError::Repo(GetRepoFileError::InvalidUTF8)),
+                };
+                //Files starting with a '.' are used internally (e.g. for the FileMetadata)
+                if v.starts_with(".") {
+                    tracing::info!("Skipping hidden bundle entry {}", name.display());
+                    return Ok(());
+                }
+                relative.push(v);
+            },
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return Err(ExtractError::Repo(GetRepoFileError::BadRequestPath)),
+        }
+    }
+    let file_name = match relative.file_name().and_then(|v|v.to_str()) {
+        Some(v) => v,
+        None => return Ok(()),
+    };
+    if !is_deployable(file_name) {
+        return Ok(());
+    }
+
+    let path = prefix.join(&relative);
+    if !seen.insert(path.clone()) {
+        return Err(ExtractError::BadArchive(format!("{}: The bundle contains this file multiple times", relative.display())));
+    }
+    if entries.len() >= MAX_BUNDLE_ENTRIES {
+        return Err(ExtractError::BadArchive(format!("The bundle contains more than {MAX_BUNDLE_ENTRIES} files")));
+    }
+    //Nothing is created at the final location yet, so that a failed deploy leaves no directories behind.
+    let temp_path = temp_file::temp_path(&repo_dir.join(file_name));
+    let mut file = match std::fs::File::create_new(&temp_path) {
+        Ok(v) => v,
+        Err(err) => {
+            tracing::error!("Failed to create temporary file {}: {err}", temp_path.display());
+            return Err(ExtractError::Repo(GetRepoFileError::FileCreateFailed));
+        }
+    };
+    entries.push(BundleEntry {
+        path,
+        source: temp_path,
+    });
+    match std::io::copy(&mut data.take(*remaining + 1), &mut file) {
+        Ok(v) if v > *remaining => Err(ExtractError::Entry(relative, GetRepoFileError::PutFileTooLarge)),
+        Ok(v) => {
+            *remaining -= v;
+            Ok(())
+        },
+        Err(err) => {
+            tracing::error!("Failed to extract bundle entry {}: {err}", relative.display());
+            Err(ExtractError::BadArchive(format!("{}: Failed to read the bundle: {err}", relative.display())))
+        }
+    }
+}
+
+fn bad_archive(err: &dyn std::fmt::Display) -> ExtractError {
+    ExtractError::BadArchive(format!("Failed to read the bundle: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use crate::audit::Actor;
+    use crate::repository::Repository;
+    use super::{deploy_entries, BundleEntry};
+
+    /// Reads all directories and files below `dir`, so that states of a repository can be compared.
+    fn read_tree(dir: &Path) -> BTreeMap<PathBuf, Option<Vec<u8>>> {
+        let mut out = BTreeMap::new();
+        let mut to_visit = vec![dir.to_path_buf()];
+        while let Some(current) = to_visit.pop() {
+            for entry in std::fs::read_dir(&current).expect("readable directory") {
+                let path = entry.expect("readable directory entry").path();
+                let relative = path.strip_prefix(dir).expect("path below dir").to_path_buf();
+                if path.is_dir() {
+                    out.insert(relative, None);
+                    to_visit.push(path);
+                } else {
+                    out.insert(relative, Some(std::fs::read(&path).expect("readable file")));
+                }
+            }
+        }
+        out
+    }
+
+    fn write_entries(sources: &Path, files: &[(&str, &str)]) -> Vec<BundleEntry> {
+        std::fs::create_dir_all(sources).expect("sources directory");
+        files.iter().enumerate().map(|(i, (path, contents))|{
+            let source = sources.join(i.to_string());
+            std::fs::write(&source, contents).expect("writable source");
+            BundleEntry {
+                path: PathBuf::from(path),
+                source,
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn failed_deploy_is_rolled_back() {
+        let dir = std::env::temp_dir().join(format!("maven-repo-bundle-rollback-{}", std::process::id()));
+        let repo_dir = dir.join("repo");
+        std::fs::create_dir_all(&repo_dir).expect("repository directory");
+        let repo = Arc::<str>::from(repo_dir.to_str().expect("UTF-8 temporary directory"));
+        let config = Arc::new(Repository::default());
+        let actor = Actor::new(None);
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().expect("runtime");
+
+        let result = runtime.block_on(async {
+            let deployed = write_entries(&dir.join("deployed"), &[
+                ("com/example/foo/1.0/foo-1.0.jar", "release"),
+                ("com/example/foo/2.0-SNAPSHOT/foo-2.0-20240101.120000-1.jar", "snapshot"),
+            ]);
+            deploy_entries(&repo, &config, &actor, &deployed).await.expect("deployable bundle");
+            let before = read_tree(&repo_dir);
+
+            //Releases can't be redeployed, so the duplicate fails, once the files before it were moved to their final locations.
+            let failing = write_entries(&dir.join("failing"), &[
+                ("com/example/foo/2.0-SNAPSHOT/foo-2.0-20240101.120000-1.jar", "replaced snapshot"),
+                ("com/example/foo/3.0/foo-3.0.jar", "new release"),
+                ("com/example/bar/1.0/bar-1.0.jar", "new artifact"),
+                ("com/example/foo/3.0/foo-3.0.jar", "duplicate release"),
+            ]);
+            let status = deploy_entries(&repo, &config, &actor, &failing).await.err().map(|v|v.status);
+            (before, status, read_tree(&repo_dir))
+        });
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let (before, status, after) = result;
+        assert_eq!(status, Some(actix_web::http::StatusCode::CONFLICT));
+        assert!(before.contains_key(Path::new("com/example/foo/maven-metadata.xml")));
+        assert!(before.contains_key(Path::new("com/example/foo/2.0-SNAPSHOT/maven-metadata.xml")));
+        assert_eq!(before, after);
+    }
+}
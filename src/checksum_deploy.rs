@@ -0,0 +1,224 @@
+#![cfg(feature = "put")]
+use std::collections::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use crate::err::GetRepoFileError;
+use crate::file_metadata::FileMetadata;
+use crate::path_info::CHECKSUM_EXTENSIONS;
+use crate::request_digest::RequestDigests;
+use crate::status::{Content, Return};
+
+/// Returns true, if the client asked to deploy a file by its checksum, without sending the body (`X-Checksum-Deploy: true`).
+pub fn is_checksum_deploy(headers: &actix_web::http::header::HeaderMap) -> bool {
+    headers.get("X-Checksum-Deploy")
+        .and_then(|v|v.to_str().ok())
+        .is_some_and(|v|v.trim().eq_ignore_ascii_case("true"))
+}
+
+/// Stored files by their digests, so that a checksum deploy doesn't need to search the repositories.
+/// Entries of deleted or replaced files are left behind, until a lookup finds them to be outdated.
+#[derive(Default)]
+struct DigestIndex {
+    sha1: HashMap<Vec<u8>, HashSet<PathBuf>>,
+    sha256: HashMap<Vec<u8>, HashSet<PathBuf>>,
+    blake3: HashMap<Vec<u8>, HashSet<PathBuf>>,
+    /// The digests each file is listed under, so that they can be removed, once the file is replaced.
+    files: HashMap<PathBuf, StoredDigests>,
+}
+/// Digests of a stored file. Files stored by older versions might lack some of them.
+#[derive(Default)]
+pub struct StoredDigests {
+    pub sha1: Option<Vec<u8>>,
+    pub sha256: Option<Vec<u8>>,
+    pub blake3: Option<Vec<u8>>,
+}
+impl DigestIndex {
+    fn insert(&mut self, path: PathBuf, digests: StoredDigests) {
+        self.remove(&path);
+        for (map, digest) in [
+            (&mut self.sha1, &digests.sha1),
+            (&mut self.sha256, &digests.sha256),
+            (&mut self.blake3, &digests.blake3),
+        ] {
+            if let Some(digest) = digest {
+                map.entry(digest.clone()).or_default().insert(path.clone());
+            }
+        }
+        self.files.insert(path, digests);
+    }
+    fn remove(&mut self, path: &Path) {
+        let digests = match self.files.remove(path) {
+            Some(v) => v,
+            None => return,
+        };
+        for (map, digest) in [
+            (&mut self.sha1, digests.sha1),
+            (&mut self.sha256, digests.sha256),
+            (&mut self.blake3, digests.blake3),
+        ] {
+            if let Some(digest) = digest
+                && let std::collections::hash_map::Entry::Occupied(mut entry) = map.entry(digest) {
+                entry.get_mut().remove(path);
+                if entry.get().is_empty() {
+                    entry.remove();
+                }
+            }
+        }
+    }
+    /// Returns the files, which have any of the sent digests.
+    fn candidates(&self, digests: &RequestDigests) -> Vec<PathBuf> {
+        let mut out = Vec::new();
+        for (map, digest) in [
+            (&self.sha1, &digests.sha1),
+            (&self.sha256, &digests.sha256),
+            (&self.blake3, &digests.blake3),
+        ] {
+            for path in digest.as_ref().and_then(|v|map.get(v)).into_iter().flatten() {
+                if !out.contains(path) {
+                    out.push(path.clone());
+                }
+            }
+        }
+        out
+    }
+}
+static INDEX: LazyLock<Mutex<DigestIndex>> = LazyLock::new(Default::default);
+/// Set, once the files, which were stored before the server started, are in the index.
+static INDEX_BUILT: tokio::sync::OnceCell<()> = tokio::sync::OnceCell::const_new();
+
+/// Adds a stored file (path including the repository) to the index, replacing the digests of the file it overwrote.
+pub fn index_file(path: PathBuf, digests: StoredDigests) {
+    INDEX.lock().unwrap_or_else(std::sync::PoisonError::into_inner).insert(path, digests);
+}
+
+/// Starts indexing the files, which are already stored, in the background.
+pub fn spawn_index_build() {
+    tokio::spawn(build_index());
+}
+
+/// Indexes the files of all repositories once. Concurrent calls wait for the first one to finish.
+async fn build_index() {
+    INDEX_BUILT.get_or_init(||async {
+        let roots = crate::REPOSITORIES.keys().map(|v|PathBuf::from(&**v)).collect::<Vec<_>>();
+        let found = tokio::task::spawn_blocking(move ||{
+            let mut found = Vec::new();
+            for root in roots {
+                if let Err(err) = index_dir(&root, &mut found) {
+                    tracing::warn!("Failed to index {} for checksum deploys: {err}", root.display());
+                }
+            }
+            found
+        }).await;
+        let found = match found {
+            Ok(v) => v,
+            Err(err) => {
+                tracing::error!("Panicked whilst indexing files for checksum deploys: {err}");
+                return;
+            }
+        };
+        let mut index = INDEX.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        for (path, digests) in found {
+            //Files stored since the server started are already indexed with their current digests.
+            if !index.files.contains_key(&path) {
+                index.insert(path, digests);
+            }
+        }
+    }).await;
+}
+
+/// Collects the digests of all files below `root` from their checksum files and the blake3 hash in their [`FileMetadata`].
+fn index_dir(root: &Path, found: &mut Vec<(PathBuf, StoredDigests)>) -> Result<(), std::io::Error> {
+    let mut to_visit = vec![root.to_path_buf()];
+    while let Some(dir) = to_visit.pop() {
+        let read_dir = match std::fs::read_dir(&dir) {
+            Ok(v) => v,
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        for entry in read_dir {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = match name.to_str() {
+                Some(v) => v,
+                None => continue,
+            };
+            if name.starts_with(".") || crate::temp_file::is_temp_file(name.as_ref()) {
+                continue;
+            }
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                to_visit.push(entry.path());
+                continue;
+            }
+            if !file_type.is_file() || Path::new(name).extension().and_then(|v|v.to_str()).is_some_and(|v|CHECKSUM_EXTENSIONS.contains(&v)) {
+                continue;
+            }
+            let digests = StoredDigests {
+                sha1: read_checksum_file(&dir.join(format!("{name}.sha1"))),
+                sha256: read_checksum_file(&dir.join(format!("{name}.sha256"))),
+                blake3: std::fs::read(dir.join(format!(".{name}.json"))).ok()
+                    .and_then(|v|serde_json::from_slice::<FileMetadata>(&v).ok())
+                    .map(|v|v.hash.as_slice().to_vec()),
+            };
+            if digests.sha1.is_some() || digests.sha256.is_some() || digests.blake3.is_some() {
+                found.push((entry.path(), digests));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_checksum_file(path: &Path) -> Option<Vec<u8>> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    contents.split_whitespace()
+        .next()
+        .and_then(|v|data_encoding::HEXLOWER_PERMISSIVE.decode(v.as_bytes()).ok())
+}
+
+/// Opens a stored file, which has the sha1, sha256 or blake3 digest sent with a checksum deploy.
+/// The deploy target is searched first, followed by all publicly readable repositories.
+/// Stored files are found through an index of their digests, which doesn't need to touch the repositories.
+/// The content still gets verified against the sent digests, while it's copied.
+pub async fn find_stored_file(repo: &str, digests: &RequestDigests) -> Result<tokio::fs::File, Return> {
+    if digests.sha1.is_none() && digests.sha256.is_none() && digests.blake3.is_none() {
+        return Err(Return {
+            status: actix_web::http::StatusCode::BAD_REQUEST,
+            content: Content::Str("A checksum deploy needs a X-Checksum-Sha1, X-Checksum-Sha256 or X-Checksum-Blake3 header"),
+            content_type: actix_web::http::header::ContentType::plaintext(),
+            header_map: None,
+        });
+    }
+    build_index().await;
+    let candidates = INDEX.lock().unwrap_or_else(std::sync::PoisonError::into_inner).candidates(digests);
+    //The deploy target is preferred over other repositories.
+    let mut candidates = candidates.into_iter()
+        .filter_map(|path|{
+            let root = path.iter().next()?.to_str()?;
+            if root == repo {
+                return Some((0, path));
+            }
+            let config = crate::REPOSITORIES.get(root)?;
+            config.publicly_readable.unwrap_or(true).then_some((1, path))
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by_key(|(order, _)|*order);
+    for (_, path) in candidates {
+        match tokio::fs::File::open(&path).await {
+            Ok(v) => return Ok(v),
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                INDEX.lock().unwrap_or_else(std::sync::PoisonError::into_inner).remove(&path);
+            },
+            Err(err) => {
+                tracing::error!("Failed to open {} for a checksum deploy: {err}", path.display());
+                return Err(GetRepoFileError::OpenFile.to_return());
+            }
+        }
+    }
+    Err(Return {
+        status: actix_web::http::StatusCode::NOT_FOUND,
+        content: Content::Str("No stored file has the checksum sent with the checksum deploy"),
+        content_type: actix_web::http::header::ContentType::plaintext(),
+        header_map: None,
+    })
+}
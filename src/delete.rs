@@ -0,0 +1,299 @@
+#![cfg(feature = "put")]
+use std::io::ErrorKind;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use crate::audit::{Actor, FileSummary};
+use crate::auth::BasicAuthentication;
+use crate::err::GetRepoFileError;
+use crate::hosted_request::{parse_hosted_request, HostedRequest};
+use crate::file_metadata::FileMetadata;
+use crate::maven_metadata::MavenMetadata;
+use crate::path_info::{is_sibling_file, PathInfo, CHECKSUM_EXTENSIONS, SIGNATURE_EXTENSION};
+use crate::put::{sibling_path, write_metadata};
+use crate::repository::Repository;
+use crate::status::{Content, Return};
+use crate::webhook::EventKind;
+
+pub async fn delete_repo_file(req: actix_web::HttpRequest, auth: Result<BasicAuthentication, Return>, client_ip: Option<IpAddr>) -> Return {
+    let HostedRequest { repo, config, path, str_path, auth, staging_guard: _staging_guard } = match parse_hosted_request(&req, auth, client_ip, "delete", "It's forbidden to delete from a repo, which has remotes.").await {
+        Ok(v) => v,
+        Err(err) => return err,
+    };
+
+    let mut actor = Actor::new(client_ip);
+    let authorized = config.check_auth(actix_web::http::Method::DELETE, auth.clone(), &str_path);
+    actor.authenticated(auth.as_ref(), &authorized);
+    let (summary, ret) = match authorized {
+        Err(err) => (FileSummary::default(), err),
+        Ok(_) => {
+            let summary = FileSummary::of(&Path::new(&*repo).join(&path)).await;
+            let changed = crate::webhook::files_to_delete(&repo, &config, &path).await;
+            let ret = delete_path(&repo, &config, &path).await;
+            if ret.status.is_success() {
+                crate::events::publish(&repo, crate::events::ChangeKind::Deleted, &path);
+                crate::webhook::notify(&repo, &config, EventKind::Delete, &changed);
+            }
+            (summary, ret)
+        }
+    };
+    crate::audit::record(&repo, &actor, "delete", &path, &summary, ret.status).await;
+    ret
+}
+
+/// Deletes the file or directory at `path` of the repository, keeping the maven-metadata up to date.
+pub async fn delete_path(repo: &str, config: &Repository, path: &Path) -> Return {
+    let file_path = Path::new(repo).join(path);
+    match tokio::fs::metadata(&file_path).await {
+        Ok(v) if v.is_dir() => return delete_dir(repo, config, path, &file_path).await,
+        Ok(_) => {},
+        Err(err) => return match err.kind() {
+            ErrorKind::NotFound => GetRepoFileError::NotFound.to_return(),
+            _ => {
+                tracing::error!("Failed to query metadata of {} for deletion: {err}", file_path.display());
+                GetRepoFileError::OpenFile.to_return()
+            }
+        },
+    }
+
+    //Checksums and Signatures don't influence the maven-metadata, so they can just be removed.
+    if is_sibling_file(&file_path) {
+        return match remove_file_with_siblings(&file_path).await {
+            Ok(()) => deleted(),
+            Err(err) => err,
+        };
+    }
+
+    match delete_artifact_file(repo, config, path, &file_path).await {
+        Ok(()) => deleted(),
+        Err(err) => err,
+    }
+}
+
+/// Deletes an artifact file (including its siblings) and removes it from the maven-metadata.
+pub async fn delete_artifact_file(repo: &str, config: &Repository, path: &Path, file_path: &Path) -> Result<(), Return> {
+    let info = match PathInfo::parse(path) {
+        Ok(v) => v,
+        Err(err) => return Err(err),
+    };
+    //This locks the maven-metadata files, so that no concurrent deployment can interfere
+    let metadata = match info.get_merged_metadata(repo, actix_web::http::Method::DELETE).await {
+        Ok(v) => v,
+        Err(err) => return Err(err),
+    };
+
+    match remove_file_with_siblings(file_path).await {
+        Ok(()) => {},
+        //The file is already gone, but might still be listed in the maven-metadata.
+        Err(err) if err.status == actix_web::http::StatusCode::NOT_FOUND => {},
+        Err(err) => return Err(err),
+    }
+
+    let version_dir = match file_path.parent() {
+        Some(v) => v,
+        None => return Err(GetRepoFileError::BadRequestPath.to_return()),
+    };
+    let has_artifacts = match dir_has_artifacts(version_dir).await {
+        Ok(v) => v,
+        Err(err) => {
+            tracing::error!("Failed to read directory {} after deleting {}: {err}", version_dir.display(), file_path.display());
+            return Err(GetRepoFileError::ReadDirectory.to_return());
+        }
+    };
+    //Releases only get removed from the maven-metadata, once the last file of that version is gone.
+    //Snapshots have a per-file entry, which always needs to be removed.
+    if info.snapshot.is_some() || !has_artifacts {
+        let max_file_size = config.max_file_size.unwrap_or(crate::DEFAULT_MAX_FILE_SIZE);
+        match write_metadata(metadata, max_file_size).await {
+            Ok(()) => {},
+            Err(err) => return Err(err),
+        }
+    }
+    if !has_artifacts && info.snapshot.is_none() {
+        //The directory might still contain files created by other tools, so a failure here isn't an error.
+        if let Err(err) = tokio::fs::remove_dir(version_dir).await {
+            tracing::info!("Not removing version directory {}: {err}", version_dir.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Recursively deletes a version, artifact or group directory.
+/// If a version directory is deleted, the version also gets removed from the artifact's maven-metadata.
+async fn delete_dir(repo: &str, config: &Repository, path: &Path, dir_path: &Path) -> Return {
+    if path.as_os_str().is_empty() {
+        return Return {
+            status: actix_web::http::StatusCode::BAD_REQUEST,
+            content: Content::Str("Refusing to delete the root of a repository"),
+            content_type: actix_web::http::header::ContentType::plaintext(),
+            header_map: None,
+        }
+    }
+    //This locks the maven-metadata file, so that no concurrent deployment can interfere
+    let metadata = match parse_version_dir(repo, path).await {
+        Some(info) => match info.get_merged_metadata(repo, actix_web::http::Method::DELETE).await {
+            Ok(v) => v,
+            Err(err) => return err,
+        },
+        None => Vec::new(),
+    };
+
+    match tokio::fs::remove_dir_all(dir_path).await {
+        Ok(()) => {},
+        Err(err) if err.kind() == ErrorKind::NotFound => return GetRepoFileError::NotFound.to_return(),
+        Err(err) => {
+            tracing::error!("Failed to recursively delete {}: {err}", dir_path.display());
+            return Return{
+                status: actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+                content: Content::Str("Failed to delete directory"),
+                content_type: actix_web::http::header::ContentType::plaintext(),
+                header_map: None,
+            }
+        }
+    }
+
+    //The last version of an artifact was deleted, so the artifact is gone entirely.
+    if metadata.iter().any(|(_, _, metadata, _)|metadata.versioning.versions.as_ref().is_none_or(|v|v.version.is_empty())) {
+        for (metadata_path, _, _, _) in &metadata {
+            match remove_file_with_siblings(metadata_path).await {
+                Ok(()) => {},
+                Err(err) => return err,
+            }
+            if let Some(parent) = metadata_path.parent()
+                && let Err(err) = tokio::fs::remove_dir(parent).await {
+                tracing::info!("Not removing artifact directory {}: {err}", parent.display());
+            }
+        }
+        return deleted();
+    }
+
+    let max_file_size = config.max_file_size.unwrap_or(crate::DEFAULT_MAX_FILE_SIZE);
+    match write_metadata(metadata, max_file_size).await {
+        Ok(()) => deleted(),
+        Err(err) => err,
+    }
+}
+
+/// Returns the [`PathInfo`] of the version, if `path` is a version directory listed in the artifact's maven-metadata.
+async fn parse_version_dir<'a>(repo: &str, path: &'a Path) -> Option<PathInfo<'a>> {
+    let mut components = path.iter().rev().map(|v|v.to_str());
+    let version = components.next()??;
+    let artifact = components.next()??;
+    let mut group = components.collect::<Option<Vec<_>>>()?;
+    if group.is_empty() {
+        return None;
+    }
+    group.reverse();
+
+    let metadata_path = Path::new(repo).join(path.parent()?).join("maven-metadata.xml");
+    let contents = match tokio::fs::read_to_string(&metadata_path).await {
+        Ok(v) => v,
+        Err(err) => {
+            if err.kind() != ErrorKind::NotFound {
+                tracing::warn!("Failed to read maven-metadata {}: {err}", metadata_path.display());
+            }
+            return None;
+        }
+    };
+    let metadata: MavenMetadata = match quick_xml::de::from_str(&contents) {
+        Ok(v) => v,
+        Err(err) => {
+            tracing::warn!("Failed to parse maven-metadata.xml {}: {err}", metadata_path.display());
+            return None;
+        }
+    };
+    if metadata.artifact_id != artifact || !metadata.versioning.versions.is_some_and(|v|v.version.contains(version)) {
+        return None;
+    }
+
+    //The version directory is the full version (including '-SNAPSHOT'), which is what the artifact's maven-metadata lists.
+    Some(PathInfo{
+        group,
+        artifact,
+        version,
+        snapshot: None,
+        classifier: None,
+        extension: None,
+    })
+}
+
+fn deleted() -> Return {
+    Return{
+        status: actix_web::http::StatusCode::NO_CONTENT,
+        content: Content::None,
+        content_type: actix_web::http::header::ContentType::plaintext(),
+        header_map: None,
+    }
+}
+
+/// Removes `file_path`, its checksums, signature and the [`FileMetadata`] of all of those.
+pub async fn remove_file_with_siblings(file_path: &Path) -> Result<(), Return> {
+    match tokio::fs::remove_file(file_path).await {
+        Ok(()) => {},
+        Err(err) if err.kind() == ErrorKind::NotFound => return Err(GetRepoFileError::NotFound.to_return()),
+        Err(err) => {
+            tracing::error!("Failed to delete {}: {err}", file_path.display());
+            return Err(Return{
+                status: actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+                content: Content::Str("Failed to delete file"),
+                content_type: actix_web::http::header::ContentType::plaintext(),
+                header_map: None,
+            })
+        }
+    }
+
+    let mut siblings = Vec::with_capacity(2 * (CHECKSUM_EXTENSIONS.len() + 1) + 1);
+    for extension in CHECKSUM_EXTENSIONS {
+        siblings.push(sibling_path(file_path, extension));
+    }
+    if !is_sibling_file(file_path) {
+        let signature = sibling_path(file_path, SIGNATURE_EXTENSION);
+        for extension in CHECKSUM_EXTENSIONS {
+            siblings.push(sibling_path(&signature, extension));
+        }
+        siblings.push(signature);
+    }
+    for sibling in siblings.iter().map(PathBuf::as_path).chain(core::iter::once(file_path)) {
+        let metadata_path = match FileMetadata::file_path_to_metadata_path(sibling) {
+            Ok(v) => v,
+            Err(err) => {
+                tracing::error!("Failed to get metadata path of {}: {err}", sibling.display());
+                continue;
+            }
+        };
+        for path in [sibling, metadata_path.as_path()] {
+            if path == file_path {
+                continue;
+            }
+            match tokio::fs::remove_file(path).await {
+                Ok(()) => {},
+                Err(err) if err.kind() == ErrorKind::NotFound => {},
+                Err(err) => {
+                    tracing::error!("Failed to delete {} after deleting {}: {err}", path.display(), file_path.display());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks, if a directory still contains any files, which are not checksums, signatures, maven-metadata or hidden files.
+async fn dir_has_artifacts(dir: &Path) -> Result<bool, std::io::Error> {
+    let mut read_dir = match tokio::fs::read_dir(dir).await {
+        Ok(v) => v,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err),
+    };
+    while let Some(entry) = read_dir.next_entry().await? {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(".") || name.starts_with("maven-metadata.xml") {
+            continue;
+        }
+        if is_sibling_file(Path::new(&*name)) {
+            continue;
+        }
+        return Ok(true);
+    }
+    Ok(false)
+}
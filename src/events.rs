@@ -0,0 +1,225 @@
+use std::collections::{HashSet, VecDeque};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use actix_web::web::Bytes;
+use futures::Stream;
+use tokio::sync::broadcast;
+use crate::repository::{get_repo_look_locations, Repository};
+use crate::status::{Content, Return};
+
+/// How many past events are kept for clients resuming from a sequence number.
+const BACKLOG_SIZE: usize = 4096;
+/// Interval of comments sent on idle streams, so that proxies don't close them.
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, serde_derive::Serialize)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(not(feature = "put"), allow(dead_code))]
+pub enum ChangeKind {
+    /// A file was deployed to a hosted repository
+    Deployed,
+    /// A file or directory was deleted from a hosted repository
+    Deleted,
+    /// A file was downloaded from an upstream and stored for the first time
+    Cached,
+    /// Revalidating a stored file with its upstream yielded new content
+    Revalidated,
+}
+impl ChangeKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Deployed => "deployed",
+            Self::Deleted => "deleted",
+            Self::Cached => "cached",
+            Self::Revalidated => "revalidated",
+        }
+    }
+}
+
+#[derive(Debug, serde_derive::Serialize)]
+struct ChangeEvent {
+    seq: u64,
+    event: ChangeKind,
+    repository: Box<str>,
+    path: Box<str>,
+    timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// Events of all repositories share one sequence, so that a stream of a group repository can be resumed like any other.
+struct Feed {
+    next_seq: u64,
+    backlog: VecDeque<Arc<ChangeEvent>>,
+    sender: broadcast::Sender<Arc<ChangeEvent>>,
+}
+impl Feed {
+    /// Returns the events after `after`, and whether events between `after` and the first returned event are unknown.
+    /// The receiver gets every event published afterwards.
+    fn subscribe(&self, after: Option<u64>) -> (Vec<Arc<ChangeEvent>>, Option<u64>, broadcast::Receiver<Arc<ChangeEvent>>) {
+        let receiver = self.sender.subscribe();
+        let after = match after {
+            Some(v) => v,
+            None => return (Vec::new(), None, receiver),
+        };
+        let first_known = self.backlog.front().map_or(self.next_seq, |v|v.seq);
+        let reset = (after.saturating_add(1) < first_known || after >= self.next_seq).then(||first_known.saturating_sub(1));
+        let events = self.backlog.iter()
+            .filter(|v|v.seq > after)
+            .cloned()
+            .collect();
+        (events, reset, receiver)
+    }
+}
+
+/// Sequence numbers start at the current time, so that they keep increasing across restarts,
+/// and a client resuming from before a restart learns that it missed events.
+static FEED: LazyLock<Mutex<Feed>> = LazyLock::new(||Mutex::new(Feed {
+    next_seq: u64::try_from(chrono::Utc::now().timestamp_micros()).unwrap_or_default(),
+    backlog: VecDeque::with_capacity(BACKLOG_SIZE),
+    sender: broadcast::channel(BACKLOG_SIZE).0,
+}));
+
+/// Records a change of `path` in `repo` and sends it to the connected event streams.
+pub fn publish(repo: &str, kind: ChangeKind, path: &Path) {
+    let path = path.to_string_lossy();
+    let mut feed = match FEED.lock() {
+        Ok(v) => v,
+        Err(err) => err.into_inner(),
+    };
+    let event = Arc::new(ChangeEvent {
+        seq: feed.next_seq,
+        event: kind,
+        repository: Box::from(repo),
+        path: Box::from(path.trim_start_matches('/')),
+        timestamp: chrono::Utc::now(),
+    });
+    feed.next_seq += 1;
+    if feed.backlog.len() >= BACKLOG_SIZE {
+        feed.backlog.pop_front();
+    }
+    feed.backlog.push_back(event.clone());
+    //Fails only if nobody is listening
+    let _ = feed.sender.send(event);
+}
+
+/// Body of an event stream.
+pub struct EventStream(Pin<Box<dyn Stream<Item = Result<Bytes, std::io::Error>> + Send>>);
+impl std::fmt::Debug for EventStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EventStream")
+    }
+}
+impl Stream for EventStream {
+    type Item = Result<Bytes, std::io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Option<Self::Item>> {
+        self.0.as_mut().poll_next(cx)
+    }
+}
+
+struct Subscription {
+    repos: HashSet<Arc<str>>,
+    prefix: Box<str>,
+    last_seq: Option<u64>,
+    pending: VecDeque<Bytes>,
+    receiver: broadcast::Receiver<Arc<ChangeEvent>>,
+}
+impl Subscription {
+    fn queue(&mut self, event: &ChangeEvent) {
+        self.last_seq = Some(event.seq);
+        if !self.repos.contains(&*event.repository) || !event.path.starts_with(&*self.prefix) {
+            return;
+        }
+        let data = match serde_json::to_string(event) {
+            Ok(v) => v,
+            Err(err) => {
+                tracing::error!("Failed to serialize change event {event:?}: {err}");
+                return;
+            }
+        };
+        self.pending.push_back(Bytes::from(format!("id: {}\nevent: {}\ndata: {data}\n\n", event.seq, event.event.as_str())));
+    }
+
+    /// Queues the events after `after`, preceded by a `reset` event if some of them are no longer known.
+    fn resume(&mut self, after: Option<u64>) {
+        let (events, reset, receiver) = match FEED.lock() {
+            Ok(v) => v,
+            Err(err) => err.into_inner(),
+        }.subscribe(after);
+        self.receiver = receiver;
+        if let Some(seq) = reset {
+            self.last_seq = Some(seq);
+            self.pending.push_back(Bytes::from(format!("id: {seq}\nevent: reset\ndata: {{\"seq\":{seq}}}\n\n")));
+        }
+        for event in events {
+            self.queue(&event);
+        }
+    }
+
+    async fn next(mut self) -> Option<(Result<Bytes, std::io::Error>, Self)> {
+        loop {
+            if let Some(v) = self.pending.pop_front() {
+                return Some((Ok(v), self));
+            }
+            match tokio::time::timeout(KEEP_ALIVE, self.receiver.recv()).await {
+                Err(_) => return Some((Ok(Bytes::from_static(b": keep-alive\n\n")), self)),
+                Ok(Ok(event)) => self.queue(&event),
+                //The backlog is larger than the channel, so a lagging stream can usually catch up from it.
+                Ok(Err(broadcast::error::RecvError::Lagged(_))) => {
+                    let after = self.last_seq;
+                    self.resume(after);
+                },
+                Ok(Err(broadcast::error::RecvError::Closed)) => return None,
+            }
+        }
+    }
+}
+
+/// Streams the changes of the repository below `prefix` as server-sent events.
+/// Group repositories include the changes of the repositories they look into.
+/// Clients resume with the `Last-Event-ID` header or `?events=<seq>`. If events after that sequence number were dropped,
+/// e.g. because of a restart, the stream starts with a `reset` event, after which clients should re-read what they follow.
+pub fn serve_events(req: &actix_web::HttpRequest, repo: &Arc<str>, config: &Arc<Repository>, prefix: &str, value: &str) -> Return {
+    let after = if !value.is_empty() {
+        Some(value)
+    } else {
+        req.headers().get("Last-Event-ID").and_then(|v|v.to_str().ok())
+    };
+    let after = match after.map(|v|v.trim().parse::<u64>()) {
+        None => None,
+        Some(Ok(v)) => Some(v),
+        Some(Err(_)) => return Return {
+            status: actix_web::http::StatusCode::BAD_REQUEST,
+            content: Content::Str("The sequence number to resume from is not a number"),
+            content_type: actix_web::http::header::ContentType::plaintext(),
+            header_map: None,
+        },
+    };
+
+    let (repos, _) = get_repo_look_locations(repo, config);
+    let mut prefix = prefix.to_owned();
+    if !prefix.is_empty() {
+        prefix.push('/');
+    }
+    let mut subscription = Subscription {
+        repos: repos.into_iter().map(|(v, _)|v).collect(),
+        prefix: prefix.into_boxed_str(),
+        last_seq: after,
+        pending: VecDeque::new(),
+        receiver: broadcast::channel(1).1,
+    };
+    subscription.resume(after);
+    //Makes clients see the response, before the first change happens.
+    subscription.pending.push_front(Bytes::from_static(b": connected\n\n"));
+
+    let mut header_map = actix_web::http::header::HeaderMap::new();
+    header_map.insert(actix_web::http::header::CACHE_CONTROL, actix_web::http::header::HeaderValue::from_static("no-cache"));
+    header_map.insert(actix_web::http::header::HeaderName::from_static("x-accel-buffering"), actix_web::http::header::HeaderValue::from_static("no"));
+    Return {
+        status: actix_web::http::StatusCode::OK,
+        content: Content::Events(EventStream(Box::pin(futures::stream::unfold(subscription, Subscription::next)))),
+        content_type: actix_web::http::header::ContentType(actix_web::mime::TEXT_EVENT_STREAM),
+        header_map: Some(header_map),
+    }
+}
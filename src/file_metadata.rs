@@ -3,9 +3,20 @@ use std::ffi::OsString;
 use std::io::{ErrorKind, Read, Write};
 use std::path::{Path, PathBuf};
 use reqwest::{Response};
+use crate::events::ChangeKind;
 use crate::remote::{get_remote_url, read_remotes};
 use crate::repository::{RemoteUpstream, Repository, Upstream};
 
+/// A file of a repository, whose [`FileMetadata`] gets validated.
+#[derive(Clone, Copy)]
+pub struct RepoFile<'a> {
+    pub config: &'a Repository,
+    /// Name of the repository
+    pub repo: &'a str,
+    /// Path of the file, relative to the repository
+    pub str_path: &'a str,
+}
+
 #[derive(Debug, Clone, serde_derive::Deserialize, serde_derive::Serialize, Eq, PartialEq)]
 #[non_exhaustive]
 pub struct FileMetadata {
@@ -62,14 +73,14 @@ impl FileMetadata {
 
     #[inline]
     pub async fn validate(
-        config: &Repository,
-        str_path: &str,
+        repo_file: RepoFile<'_>,
         path: &Path,
         mem: &mut memmap2::Mmap,
         file: &mut tokio::fs::File,
         metadata: &std::fs::Metadata,
         hash: &blake3::Hash
     ) -> Result<Option<Self>, Vec<anyhow::Error>> {
+        let RepoFile { config, str_path, .. } = repo_file;
         let self_ = match Self::open(path).await {
             Ok(v) => {
                 let upstream = v.get_upstream(config);
@@ -97,7 +108,7 @@ impl FileMetadata {
                 _ => return Err(vec![anyhow::Error::from(err)])
             },
         };
-        Self::new_file_impl(self_, config, str_path, path, mem, file, metadata, hash).await
+        Self::new_file_impl(self_, repo_file, path, mem, file, metadata, hash).await
     }
 
 
@@ -159,7 +170,7 @@ impl FileMetadata {
         task.await.unwrap_or_else(|err|Err(err.into()))
     }
 
-    fn file_path_to_metadata_path(
+    pub fn file_path_to_metadata_path(
         path: &Path,
     ) -> Result<PathBuf, std::io::Error> {
         let mut path = path.to_path_buf();
@@ -178,14 +189,14 @@ impl FileMetadata {
 
     async fn new_file_impl<'a>(
         self_: Option<Self>,
-        config: &Repository,
-        str_path: &str,
+        repo_file: RepoFile<'_>,
         path: &Path,
         mem: &'a mut memmap2::Mmap,
         file: &mut tokio::fs::File,
         metadata: &std::fs::Metadata,
         hash: &blake3::Hash,
     ) -> Result<Option<Self>, Vec<anyhow::Error>> {
+        let RepoFile { config, repo, str_path } = repo_file;
         let mut errors = Vec::new();
         let mut headers = if let Some(self_) = self_ {
             let headers = self_.get_request_headers();
@@ -199,10 +210,13 @@ impl FileMetadata {
                 Err(mut err) => {
                     errors.append(&mut err);
                 },
-                Ok((url, resp, new_hash)) => {
+                Ok((url, resp, new_hash, changed)) => {
                     let mut meta = FileMetadata::new_response(Box::from(url), &resp, new_hash.unwrap_or(*hash).as_bytes());
                     meta.local_last_modified = core::cmp::max(self_.local_last_modified, meta.local_last_modified);
                     meta.write(path).await.map_err(|err|vec![anyhow::Error::from(err).context("Failed to write file")])?;
+                    if changed {
+                        crate::events::publish(repo, ChangeKind::Revalidated, Path::new(str_path));
+                    }
                     return Ok(Some(meta));
                 }
             }
@@ -241,9 +255,12 @@ impl FileMetadata {
             Err(mut err) => {
                 errors.append(&mut err);
             },
-            Ok((url, resp, new_hash)) => {
+            Ok((url, resp, new_hash, changed)) => {
                 let meta = FileMetadata::new_response(url.into_boxed_str(), &resp, new_hash.unwrap_or(*hash).as_bytes());
                 meta.write(path).await.map_err(|err|vec![anyhow::Error::from(err).context("Failed to write file")])?;
+                if changed {
+                    crate::events::publish(repo, ChangeKind::Revalidated, Path::new(str_path));
+                }
                 return Ok(Some(meta));
             }
         }
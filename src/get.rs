@@ -2,16 +2,16 @@ mod local;
 mod remote;
 mod interal_impl;
 mod header;
+mod range;
+mod listing;
 
-use std::borrow::Cow;
 use std::collections::HashMap;
-use std::fs::FileType;
 use std::path::{Component, PathBuf};
 use tokio::time::Instant;
 use crate::auth::BasicAuthentication;
 use crate::status::{Content, Return};
 use crate::err::GetRepoFileError;
-use crate::{RequestHeaders, REPOSITORIES};
+use crate::RequestHeaders;
 use crate::server_timings::AsServerTimingDuration;
 
 use local::serve_repository_stored_path;
@@ -123,8 +123,8 @@ pub async fn get_repo_file(req: actix_web::HttpRequest, auth: Result<BasicAuthen
     tracing::info!("get_repo_file: {repo}: get_repository_config_store took {}µs", (next-start).as_micros());
     core::mem::swap(&mut start, &mut next);
 
-    let (repo, config) = match REPOSITORIES.get_key_value(repo) {
-        Some((k, v)) => (&**k, v),
+    let (repo, config) = match crate::repository::get_repo(repo) {
+        Some(v) => v,
         None => {
             let mut ret = GetRepoFileError::NotFound.to_return();
 
@@ -158,12 +158,21 @@ pub async fn get_repo_file(req: actix_web::HttpRequest, auth: Result<BasicAuthen
     tracing::info!("get_repo_file: {repo}: auth check took {}µs", (next-start).as_micros());
     core::mem::swap(&mut start, &mut next);
 
+    //`?events[=<seq>]` follows the changes below the requested path, instead of serving it.
+    let query = req.query_string();
+    let action = query.split("&").next().unwrap_or_default();
+    let (action, value) = action.split_once("=").unwrap_or((action, ""));
+    if action == "events" {
+        return crate::events::serve_events(&req, &repo, &config, str_path, value);
+    }
+
     let resolve_impl = resolve_impl(&repo, path.as_path(), str_path, &config, &mut timings, &request_headers).await;
     next = Instant::now();
     timings.push_iter_nodelim([r#"resolveImpl;dur="#, (next-start).as_server_timing_duration().to_string().as_str(), r#";desc="Total Resolve Implementation""#]);
     tracing::info!("get_repo_file: {repo}: get_repo_file_impl check took {}µs", (next-start).as_micros());
     core::mem::swap(&mut start, &mut next);
 
+    let json_listing = listing::wants_json(req.query_string(), &request_headers.headers);
     let (metadata, content, hash, mut timing, dir_listing) = match resolve_impl {
         Ok(StoredRepoPath::Mmap{metadata, data, hash, timing}) => (vec![metadata], Content::Mmap(data), hash, timing, false),
         Ok(StoredRepoPath::IsADir) => {
@@ -186,11 +195,53 @@ pub async fn get_repo_file(req: actix_web::HttpRequest, auth: Result<BasicAuthen
             }
             return ret;
         },
+        Ok(StoredRepoPath::Redirect(file_name)) => {
+            let mut ret = Return {
+                status: actix_web::http::StatusCode::TEMPORARY_REDIRECT,
+                content: Content::Empty,
+                content_type: actix_web::http::header::ContentType::plaintext(),
+                header_map: None,
+            };
+            //The file-name is relative to the directory of the requested file.
+            match actix_web::http::header::HeaderValue::from_str(file_name.as_str()) {
+                Ok(v) => {ret.header_map.get_or_insert_default().append(actix_web::http::header::LOCATION, v);}
+                Err(err) => {
+                    tracing::warn!("Cannot convert '{}' to a header-value: {err}", file_name);
+                }
+            }
+            config.apply_cache_control(&mut ret);
+            return ret;
+        },
         Ok(StoredRepoPath::DirListing{metadata, entries}) => {
-            let out = entries_to_content(&entries);
+            header_map.append(actix_web::http::header::VARY, actix_web::http::header::HeaderValue::from_static("Accept"));
+            let out = if json_listing {
+                match listing::entries_to_json(&entries, str_path, req.query_string()) {
+                    Ok(v) => v,
+                    Err(err) => {
+                        tracing::error!("Failed to serialize the directory listing of {str_path}: {err}");
+                        return Return {
+                            status: actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+                            content: Content::Str("Failed to serialize the directory listing"),
+                            content_type: actix_web::http::header::ContentType::plaintext(),
+                            header_map: None,
+                        };
+                    }
+                }
+            } else {
+                listing::entries_to_content(&entries, !str_path.is_empty(), req.query_string())
+            };
             let hash = blake3::Hasher::new().update(out.as_bytes()).finalize();
             (metadata, Content::String(out), hash, ServerTimings::new(), true)
         },
+        Ok(StoredRepoPath::MergedMetadata{metadata, data}) => {
+            let hash = blake3::Hasher::new().update(data.as_bytes()).finalize();
+            (metadata, Content::String(data), hash, ServerTimings::new(), false)
+        },
+        #[cfg(feature = "put")]
+        Ok(StoredRepoPath::Checksum{metadata, data, timing}) => {
+            let hash = blake3::Hasher::new().update(data.as_bytes()).finalize();
+            (vec![metadata], Content::String(data), hash, timing, false)
+        },
         Ok(StoredRepoPath::Upstream(upstream)) => {
             let mut ret = Return{
                 status: actix_web::http::StatusCode::OK,
@@ -243,6 +294,9 @@ pub async fn get_repo_file(req: actix_web::HttpRequest, auth: Result<BasicAuthen
     timings.append(&mut timing);
 
     let mut ret = header_check(&repo, &path, &config, str_path, timings, content, dir_listing, &request_headers, hash, &metadata, header_map, &mut start, &mut next).await;
+    if dir_listing && json_listing && ret.status.is_success() {
+        ret.content_type = actix_web::http::header::ContentType::json();
+    }
     config.apply_cache_control(&mut ret);
     ret
 }
@@ -255,31 +309,24 @@ enum StoredRepoPath{
     },
     IsADir,
     Upstream(reqwest::Response),
+    /// The file is stored under a different name in the same directory.
+    Redirect(String),
     DirListing{
         metadata: Vec<std::fs::Metadata>,
-        entries: HashMap<String, FileType>,
-    }
+        entries: HashMap<String, listing::ListingEntry>,
+    },
+    /// maven-metadata.xml combined from several repositories.
+    /// The metadata is of the local files, which went into it.
+    MergedMetadata{
+        metadata: Vec<std::fs::Metadata>,
+        data: String,
+    },
+    /// A missing checksum file, computed from the file it belongs to.
+    /// The metadata is of that file.
+    #[cfg(feature = "put")]
+    Checksum{
+        metadata: std::fs::Metadata,
+        data: String,
+        timing: ServerTimings,
+    },
 }
-fn entries_to_content(entries: &HashMap<String, FileType>) -> String {
-    let mut out = r#"<!DOCTYPE HTML><html><head><meta charset="utf-8"><meta name="color-scheme" content="dark light"></head><body><ul>"#.to_owned();
-    let mut v = entries.iter().map(|(key, value)|{
-        if value.is_dir() {
-            let mut key = key.clone();
-            key.push('/');
-            Cow::Owned(key)
-        } else {
-            Cow::Borrowed(key.as_str())
-        }
-    }).collect::<Vec<_>>();
-    v.sort();
-    for entry in v {
-        out.push_str(r#"<li><a href=""#);
-        out.push_str(entry.as_ref());
-        out.push_str(r#"">"#);
-        out.push_str(entry.as_ref());
-        out.push_str("</a></li>");
-    }
-    out.push_str("</ul></body></html>");
-
-    out
-}
\ No newline at end of file
@@ -1,6 +1,7 @@
 use std::path::Path;
 use tokio::time::Instant;
 use crate::etag::ETagValidator;
+use crate::get::range::apply_range;
 use crate::repository::Repository;
 use crate::RequestHeaders;
 use crate::server_timings::AsServerTimingDuration;
@@ -340,7 +341,8 @@ pub async fn header_check(
                     }
                 }
             }
-        } else {
+        //Without any known modification time (e.g. maven-metadata merged only from upstreams), the dates can't be compared.
+        } else if !modification_err.is_empty() {
             return Return{
                 status: actix_web::http::StatusCode::BAD_REQUEST,
                 content: Content::String(modification_err.iter().map(|v|format!("Could not get Modification time: {v}")).collect::<Vec<_>>().join("\n")),
@@ -349,6 +351,9 @@ pub async fn header_check(
             }
         }
     }
+    if status == actix_web::http::StatusCode::OK && !dir_listing {
+        (status, content, content_type) = apply_range(request_headers, &hash, modification_datetime, content, content_type, &mut header_map).await;
+    }
     *next = Instant::now();
     timings.push_iter_nodelim([r#"condHeader;dur="#, (*next-*start).as_server_timing_duration().to_string().as_str(), r#";desc="Parsing,Validation and Evaluation of conditional request Headers""#]);
     tracing::info!("get_repo_file: {repo}: header checks took {}µs", (*next-*start).as_micros());
@@ -1,20 +1,23 @@
 use std::collections::HashSet;
+use std::collections::hash_map::Entry;
 use std::path::{Component, Path};
 use std::sync::{Arc, LazyLock};
 use tokio::task::JoinSet;
 use tokio::time::Instant;
 use crate::err::GetRepoFileError;
+use crate::maven_metadata::MavenMetadata;
+use crate::path_info::{split_sibling_extensions, NonUniqueSnapshot};
 use crate::get::{serve_remote_repository, serve_repository_stored_path, StoredRepoPath};
 use crate::repository::{get_repo_look_locations, Repository, Upstream};
 use crate::{RequestHeaders};
 use crate::server_timings::AsServerTimingDuration;
 use crate::timings::ServerTimings;
 
-pub async fn resolve_impl(repo: &'static str, path: &Path, str_path: &str, config: &'static Repository, timings: &mut ServerTimings, request_headers: &RequestHeaders) -> Result<StoredRepoPath, Vec<GetRepoFileError>> {
+pub async fn resolve_impl(repo: &Arc<str>, path: &Path, str_path: &str, config: &Arc<Repository>, timings: &mut ServerTimings, request_headers: &RequestHeaders) -> Result<StoredRepoPath, Vec<GetRepoFileError>> {
     let mut start = Instant::now();
     let mut next;
 
-    let (configs, mut errors) = get_repo_look_locations(repo, &config);
+    let (configs, mut errors) = get_repo_look_locations(repo, config);
     next = Instant::now();
     timings.push_iter_nodelim([r#"resolveImplGetLocalRepoConfigs;dur="#, (next-start).as_server_timing_duration().to_string().as_str(), r#";desc="Resolve Implementation: Fetch all local upstream repo configs""#]);
     tracing::info!("get_repo_file_impl: {repo}: get_repo_look_locations took {}µs", (next-start).as_micros());
@@ -22,18 +25,33 @@ pub async fn resolve_impl(repo: &'static str, path: &Path, str_path: &str, confi
 
     let mut js = JoinSet::new();
 
-    let mut check_result = async |js:&mut JoinSet<_>|{
+    //Every repository of a group lists only its own versions, so maven-metadata.xml is collected from all of them and merged.
+    let merge_metadata = path.file_name().is_some_and(|v|v == "maven-metadata.xml");
+    let mut metadata_files = Vec::new();
+
+    //With `found`, files are collected there, instead of returning the first one.
+    let mut check_result = async |js:&mut JoinSet<_>, mut found: Option<&mut Vec<StoredRepoPath>>|{
         let mut out = None;
         while let Some(task) = js.join_next().await {
             match task {
                 Ok(Ok(v)) => {
+                    if let Some(found) = &mut found
+                        && matches!(v, StoredRepoPath::Mmap{..} | StoredRepoPath::Upstream(_)) {
+                        found.push(v);
+                        continue;
+                    }
                     out = match (out, v) {
                         (_, StoredRepoPath::IsADir) =>  {
                             js.abort_all();
                             return Some(StoredRepoPath::IsADir);
                         }
                         (Some(StoredRepoPath::DirListing{mut metadata, mut entries}), StoredRepoPath::DirListing{metadata: mut metadata_1, entries: entries_1}) => {
-                            entries.extend(entries_1);
+                            for (name, entry) in entries_1 {
+                                match entries.entry(name) {
+                                    Entry::Occupied(mut v) => v.get_mut().merge(entry),
+                                    Entry::Vacant(v) => { v.insert(entry); },
+                                }
+                            }
                             metadata.append(&mut metadata_1);
                             Some(StoredRepoPath::DirListing{metadata, entries})
                         }
@@ -61,10 +79,10 @@ pub async fn resolve_impl(repo: &'static str, path: &Path, str_path: &str, confi
     let str_path = Arc::<str>::from(str_path);
     for (repo, repo_config) in &configs {
         let display_dir = !config.hide_directory_listings.unwrap_or(repo_config.hide_directory_listings.unwrap_or(false));
-        js.spawn(serve_repository_stored_path(Path::new(&**repo).join(&path), display_dir, request_headers.has_trailing_slash, *repo_config, str_path.clone()));
+        js.spawn(serve_repository_stored_path(repo.clone(), Path::new(&**repo).join(path), display_dir, request_headers.has_trailing_slash, repo_config.clone(), str_path.clone()));
     }
 
-    if let Some(v) = check_result(&mut js).await {
+    if let Some(v) = check_result(&mut js, merge_metadata.then_some(&mut metadata_files)).await {
         next = Instant::now();
         timings.push_iter_nodelim([r#"resolveImplQueryLocalRepositoriesFound;dur="#, (next-start).as_server_timing_duration().to_string().as_str(), r#";desc="Resolve Implementation: Query local repositories for File (HIT)""#]);
         tracing::info!("get_repo_file_impl: {repo}: final resolve took took {}µs (skipped remotes, as the information could be locally sourced)", (next-start).as_micros());
@@ -142,7 +160,7 @@ pub async fn resolve_impl(repo: &'static str, path: &Path, str_path: &str, confi
 
             domain
         }));
-        for (repo, config) in configs {
+        for (repo, config) in &configs {
             for upstream in &config.upstreams {
                 let upstream = match upstream {
                     Upstream::Local(_) => continue,
@@ -152,9 +170,9 @@ pub async fn resolve_impl(repo: &'static str, path: &Path, str_path: &str, confi
                     js.spawn(serve_remote_repository(
                         upstream.clone(),
                         str_path.clone(),
-                        repo,
+                        repo.clone(),
                         remote_path.clone(),
-                        config,
+                        config.clone(),
                         request_url.clone(),
                         request_headers.client_ip
                     ));
@@ -164,7 +182,7 @@ pub async fn resolve_impl(repo: &'static str, path: &Path, str_path: &str, confi
     }
 
     //Collect requests from upstreams
-    if let Some(v) = check_result(&mut js).await {
+    if let Some(v) = check_result(&mut js, merge_metadata.then_some(&mut metadata_files)).await {
         next = Instant::now();
         timings.push_iter_nodelim([r#"resolveImplQueryRemoteRepositoriesHit;dur="#, (next-start).as_server_timing_duration().to_string().as_str(), r#";desc="Resolve Implementation: Query remote repositories for File (HIT)""#]);
         tracing::info!("get_repo_file_impl: {repo}: final resolve took took {}µs (contacted remotes)", (next-start).as_micros());
@@ -176,5 +194,131 @@ pub async fn resolve_impl(repo: &'static str, path: &Path, str_path: &str, confi
     tracing::info!("get_repo_file_impl: {repo}: final resolve took took {}µs (contacted remotes)", (next-start).as_micros());
     core::mem::swap(&mut start, &mut next);
 
+    if !metadata_files.is_empty() {
+        let out = merge_maven_metadata(metadata_files, path).await;
+        next = Instant::now();
+        timings.push_iter_nodelim([r#"resolveImplMergeMavenMetadata;dur="#, (next-start).as_server_timing_duration().to_string().as_str(), r#";desc="Resolve Implementation: Merge maven-metadata.xml of all repositories""#]);
+        tracing::info!("get_repo_file_impl: {repo}: merging maven-metadata took {}µs", (next-start).as_micros());
+        core::mem::swap(&mut start, &mut next);
+        return out.map_err(|mut v|{
+            errors.append(&mut v);
+            errors
+        });
+    }
+
+    //A non-unique snapshot file, which isn't stored as is, is served from the newest build.
+    if let Some(file_name) = resolve_non_unique_snapshot(&configs, path).await {
+        next = Instant::now();
+        timings.push_iter_nodelim([r#"resolveImplNonUniqueSnapshot;dur="#, (next-start).as_server_timing_duration().to_string().as_str(), r#";desc="Resolve Implementation: Resolve non-unique Snapshot to the newest build""#]);
+        tracing::info!("get_repo_file_impl: {repo}: resolving non-unique snapshot took {}µs", (next-start).as_micros());
+        core::mem::swap(&mut start, &mut next);
+        return Ok(StoredRepoPath::Redirect(file_name));
+    }
+
     Err(errors)
-}
\ No newline at end of file
+}
+
+/// Returns the file-name of the newest build of a non-unique snapshot file (e.g. `foo-1.0-SNAPSHOT.jar` -> `foo-1.0-20240101.120000-1.jar`),
+/// according to the version-level maven-metadata of the first local repository, which has such a build.
+/// Checksums and signatures are resolved to those of the build's file (e.g. `foo-1.0-SNAPSHOT.jar.sha1` -> `foo-1.0-20240101.120000-1.jar.sha1`).
+async fn resolve_non_unique_snapshot(configs: &[(Arc<str>, Arc<Repository>)], path: &Path) -> Option<String> {
+    let (file_name, sibling_extensions) = split_sibling_extensions(path.file_name()?.to_str()?);
+    let artifact_path = path.with_file_name(file_name);
+    let info = NonUniqueSnapshot::parse(&artifact_path)?;
+    let metadata_path = path.with_file_name("maven-metadata.xml");
+    for (repo, _) in configs {
+        let metadata_path = Path::new(&**repo).join(&metadata_path);
+        let contents = match tokio::fs::read_to_string(&metadata_path).await {
+            Ok(v) => v,
+            Err(err) => {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    tracing::warn!("Failed to read maven-metadata {}: {err}", metadata_path.display());
+                }
+                continue;
+            }
+        };
+        let metadata: MavenMetadata = match quick_xml::de::from_str(&contents) {
+            Ok(v) => v,
+            Err(err) => {
+                tracing::warn!("Failed to parse maven-metadata.xml {}: {err}", metadata_path.display());
+                continue;
+            }
+        };
+        if let Some(version) = metadata.versioning.latest_snapshot_version(info.classifier, info.extension) {
+            let mut file_name = info.unique_file_name(&version.value);
+            file_name.push_str(sibling_extensions);
+            return Some(file_name);
+        }
+    }
+    None
+}
+
+/// Merges the maven-metadata.xml files found in the local repositories and upstreams into one document.
+/// A single file is served as is. Files, which can't be parsed (e.g. the plugin listing of a group), are left out of the merge.
+/// If none of them can be parsed, the first local one is served.
+async fn merge_maven_metadata(files: Vec<StoredRepoPath>, path: &Path) -> Result<StoredRepoPath, Vec<GetRepoFileError>> {
+    let files = match <[_; 1]>::try_from(files) {
+        Ok([file]) => return Ok(file),
+        Err(files) => files,
+    };
+    let mut merged: Option<MavenMetadata> = None;
+    let mut file_metadata = Vec::new();
+    let mut fallback = None;
+    let mut errors = Vec::new();
+    for file in files {
+        let parsed = match file {
+            StoredRepoPath::Mmap{metadata, data, hash, timing} => {
+                match std::str::from_utf8(&data).map_err(|v|v.to_string())
+                    .and_then(|v|quick_xml::de::from_str::<MavenMetadata>(v).map_err(|v|v.to_string()))
+                {
+                    Ok(v) => {
+                        file_metadata.push(metadata);
+                        v
+                    },
+                    Err(err) => {
+                        tracing::warn!("Not merging maven-metadata.xml {}: {err}", path.display());
+                        fallback.get_or_insert(StoredRepoPath::Mmap{metadata, data, hash, timing});
+                        continue;
+                    }
+                }
+            },
+            StoredRepoPath::Upstream(response) => {
+                let contents = match response.text().await {
+                    Ok(v) => v,
+                    Err(err) => {
+                        tracing::warn!("Failed to read maven-metadata.xml {} from upstream: {err}", path.display());
+                        errors.push(GetRepoFileError::UpstreamBodyReadError);
+                        continue;
+                    }
+                };
+                match quick_xml::de::from_str::<MavenMetadata>(&contents) {
+                    Ok(v) => v,
+                    Err(err) => {
+                        tracing::warn!("Not merging maven-metadata.xml {} from upstream: {err}", path.display());
+                        continue;
+                    }
+                }
+            },
+            _ => continue,
+        };
+        match &mut merged {
+            Some(merged) => merged.merge(parsed),
+            None => merged = Some(parsed),
+        }
+    }
+
+    let merged = match merged {
+        Some(v) => v,
+        None => return fallback.ok_or(errors),
+    };
+    match quick_xml::se::to_string(&merged) {
+        Ok(data) => Ok(StoredRepoPath::MergedMetadata{
+            metadata: file_metadata,
+            data,
+        }),
+        Err(err) => {
+            tracing::error!("Failed to serialize merged maven-metadata.xml {} value '{merged:#?}': {err}", path.display());
+            fallback.ok_or(errors)
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// An entry of a directory listing.
+#[derive(Debug)]
+pub struct ListingEntry {
+    pub is_dir: bool,
+    /// Size of files, directories have none
+    pub size: Option<u64>,
+    pub modified: Option<chrono::DateTime<chrono::Utc>>,
+    /// blake3 hash of files, if they were hashed since they last changed
+    pub hash: Option<blake3::Hash>,
+    /// Local repositories, which contain the entry
+    pub repos: Vec<Box<str>>,
+}
+impl ListingEntry {
+    /// Adds the repositories of an entry with the same name in another repository.
+    /// The newer entry's size and modification time are kept, and the repositories are sorted,
+    /// so that the listing doesn't depend on which repository answered first.
+    pub fn merge(&mut self, other: Self) {
+        if other.modified > self.modified {
+            self.is_dir = other.is_dir;
+            self.size = other.size;
+            self.modified = other.modified;
+            self.hash = other.hash;
+        }
+        for repo in other.repos {
+            if !self.repos.contains(&repo) {
+                self.repos.push(repo);
+            }
+        }
+        self.repos.sort();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortColumn {
+    Name,
+    Size,
+    Modified,
+}
+impl SortColumn {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Size => "size",
+            Self::Modified => "modified",
+        }
+    }
+}
+
+/// Sorting requested by `?sort=<name|size|modified>&order=<asc|desc>`.
+/// Unknown values fall back to sorting by name in ascending order.
+#[derive(Debug, Clone, Copy)]
+struct Sort {
+    column: SortColumn,
+    descending: bool,
+}
+impl Sort {
+    fn parse(query: &str) -> Self {
+        let mut sort = Self {
+            column: SortColumn::Name,
+            descending: false,
+        };
+        for (key, value) in query.split('&').filter_map(|v|v.split_once('=')) {
+            match (key, value) {
+                ("sort", "name") => sort.column = SortColumn::Name,
+                ("sort", "size") => sort.column = SortColumn::Size,
+                ("sort", "modified") => sort.column = SortColumn::Modified,
+                ("order", "asc") => sort.descending = false,
+                ("order", "desc") => sort.descending = true,
+                _ => {},
+            }
+        }
+        sort
+    }
+
+    /// Directories always come first, and entries with equal values are sorted by name.
+    fn compare(&self, (name_a, a): &(&String, &ListingEntry), (name_b, b): &(&String, &ListingEntry)) -> Ordering {
+        let ordering = match self.column {
+            SortColumn::Name => Ordering::Equal,
+            SortColumn::Size => a.size.cmp(&b.size),
+            SortColumn::Modified => a.modified.cmp(&b.modified),
+        }.then_with(||name_a.cmp(name_b));
+        let ordering = if self.descending { ordering.reverse() } else { ordering };
+        b.is_dir.cmp(&a.is_dir).then(ordering)
+    }
+}
+
+/// Renders the html listing of a directory.
+/// `has_parent` adds a link to the parent directory, which the root of a repository doesn't have.
+pub fn entries_to_content(entries: &HashMap<String, ListingEntry>, has_parent: bool, query: &str) -> String {
+    let sort = Sort::parse(query);
+    let mut entries = entries.iter().collect::<Vec<_>>();
+    entries.sort_by(|a, b|sort.compare(a, b));
+    let show_repos = entries.iter().any(|(_, v)|v.repos.len() > 1)
+        || entries.windows(2).any(|v|v[0].1.repos != v[1].1.repos);
+
+    let mut out = r#"<!DOCTYPE HTML><html><head><meta charset="utf-8"><meta name="color-scheme" content="dark light"></head><body><table><thead><tr>"#.to_owned();
+    for (column, title) in [(SortColumn::Name, "Name"), (SortColumn::Size, "Size"), (SortColumn::Modified, "Last Modified")] {
+        let order = if sort.column == column && !sort.descending { "desc" } else { "asc" };
+        let _ = write!(out, r#"<th><a href="?sort={}&amp;order={order}">{title}</a></th>"#, column.as_str());
+    }
+    if show_repos {
+        out.push_str("<th>Repository</th>");
+    }
+    out.push_str("</tr></thead><tbody>");
+    if has_parent {
+        out.push_str(r#"<tr><td><a href="../">../</a></td><td></td><td></td>"#);
+        if show_repos {
+            out.push_str("<td></td>");
+        }
+        out.push_str("</tr>");
+    }
+    for (name, entry) in entries {
+        let suffix = if entry.is_dir { "/" } else { "" };
+        let _ = write!(out, r#"<tr><td><a href="{}{suffix}">{}{suffix}</a></td>"#, percent_encode(name), escape_html(name));
+        match entry.size {
+            Some(size) => { let _ = write!(out, "<td>{size}</td>"); },
+            None => out.push_str("<td>-</td>"),
+        }
+        match entry.modified {
+            Some(modified) => { let _ = write!(out, "<td>{}</td>", modified.format("%Y-%m-%d %H:%M:%S")); },
+            None => out.push_str("<td></td>"),
+        }
+        if show_repos {
+            let repos = entry.repos.iter().map(|v|escape_html(v)).collect::<Vec<_>>();
+            let _ = write!(out, "<td>{}</td>", repos.join(", "));
+        }
+        out.push_str("</tr>");
+    }
+    out.push_str("</tbody></table></body></html>");
+
+    out
+}
+
+#[derive(Debug, serde_derive::Serialize)]
+struct JsonListing<'a> {
+    path: &'a str,
+    entries: Vec<JsonEntry<'a>>,
+}
+#[derive(Debug, serde_derive::Serialize)]
+struct JsonEntry<'a> {
+    name: &'a str,
+    #[serde(rename = "type")]
+    type_: &'static str,
+    size: Option<u64>,
+    modified: Option<chrono::DateTime<chrono::Utc>>,
+    blake3: Option<String>,
+    repositories: &'a [Box<str>],
+}
+
+/// Renders the listing of the directory `path` as json, sorted like the html listing.
+pub fn entries_to_json(entries: &HashMap<String, ListingEntry>, path: &str, query: &str) -> Result<String, serde_json::Error> {
+    let sort = Sort::parse(query);
+    let mut entries = entries.iter().collect::<Vec<_>>();
+    entries.sort_by(|a, b|sort.compare(a, b));
+    serde_json::to_string(&JsonListing {
+        path,
+        entries: entries.into_iter().map(|(name, entry)|JsonEntry {
+            name,
+            type_: if entry.is_dir { "directory" } else { "file" },
+            size: entry.size,
+            modified: entry.modified,
+            blake3: entry.hash.map(|v|v.to_hex().to_string()),
+            repositories: &entry.repos,
+        }).collect(),
+    })
+}
+
+/// Returns true, if the listing should be json instead of html,
+/// because of `?format=json`, or because the `Accept` header prefers json over html.
+pub fn wants_json(query: &str, headers: &actix_web::http::header::HeaderMap) -> bool {
+    if let Some(format) = query.split('&').find_map(|v|v.strip_prefix("format=")) {
+        return format == "json";
+    }
+    let mut json = 0.0;
+    let mut html = 0.0;
+    for value in headers.get_all(actix_web::http::header::ACCEPT).filter_map(|v|v.to_str().ok()) {
+        for media_range in value.split(',') {
+            let mut parts = media_range.split(';').map(str::trim);
+            let media_type = parts.next().unwrap_or_default().to_ascii_lowercase();
+            let quality = parts
+                .find_map(|v|v.strip_prefix("q="))
+                .and_then(|v|v.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            match media_type.as_str() {
+                "application/json" => json = f32::max(json, quality),
+                "text/html" | "text/*" | "*/*" => html = f32::max(html, quality),
+                _ => {},
+            }
+        }
+    }
+    json > html
+}
+
+fn escape_html(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for char in value.chars() {
+        match char {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(char),
+        }
+    }
+    out
+}
+
+/// Percent-encodes everything but unreserved characters (RFC 3986), so that a file-name is always a relative path segment.
+fn percent_encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => out.push(byte as char),
+            _ => { let _ = write!(out, "%{byte:02X}"); },
+        }
+    }
+    out
+}
@@ -1,17 +1,19 @@
 use std::collections::HashMap;
-use std::fs::FileType;
 use std::io::ErrorKind;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::time::Instant;
 use crate::err::GetRepoFileError;
-use crate::file_metadata::FileMetadata;
+use crate::file_metadata::{FileMetadata, RepoFile};
 use crate::get::StoredRepoPath;
+use crate::get::listing::ListingEntry;
 use crate::repository::Repository;
 use crate::server_timings::AsServerTimingDuration;
 use crate::timings::ServerTimings;
+#[cfg(feature = "put")]
+use crate::path_info::CHECKSUM_EXTENSIONS;
 
-pub async fn serve_repository_stored_path(path: PathBuf, display_dir: bool, has_trailing_slash: bool, config: &Repository, str_path: Arc<str>) -> Result<StoredRepoPath, Vec<GetRepoFileError>> {
+pub async fn serve_repository_stored_path(repo: Arc<str>, path: PathBuf, display_dir: bool, has_trailing_slash: bool, config: Arc<Repository>, str_path: Arc<str>) -> Result<StoredRepoPath, Vec<GetRepoFileError>> {
     let mut start = Instant::now();
     let mut next;
     let mut errors = Vec::new();
@@ -34,7 +36,7 @@ pub async fn serve_repository_stored_path(path: PathBuf, display_dir: bool, has_
             errors.push(GetRepoFileError::NotFound);
         } else {
             match futures::join!(
-                serve_repository_stored_dir(&path),
+                serve_repository_stored_dir(&repo, &path),
                 tokio::fs::metadata(&path)
             ) {
                 (Ok(entries), Ok(meta)) => return Ok(StoredRepoPath::DirListing {entries, metadata: vec![meta]}),
@@ -55,6 +57,22 @@ pub async fn serve_repository_stored_path(path: PathBuf, display_dir: bool, has_
         }
         Err(errors)
     } else {
+        #[cfg(feature = "put")]
+        if let Some(extension) = path.extension().and_then(|v|v.to_str()).filter(|v|CHECKSUM_EXTENSIONS.contains(v)) {
+            let checksum = match virtual_checksum(&path, extension).await {
+                Ok(v) => v,
+                Err(err) => {
+                    tracing::warn!("Failed to compute checksum file {}: {err}", path.display());
+                    None
+                }
+            };
+            next = Instant::now();
+            timing.push_iter_nodelim([r#"resolveImplLocalVirtualChecksum;dur="#, (next-start).as_server_timing_duration().to_string().as_str(), r#";desc="Resolve Impl: Local: Compute missing Checksum File""#]);
+            core::mem::swap(&mut start, &mut next);
+            if let Some((metadata, data)) = checksum {
+                return Ok(StoredRepoPath::Checksum{metadata, data, timing});
+            }
+        }
         let path = Arc::<std::path::Path>::from(path);
         let metadata = {
             let path = path.clone();
@@ -87,7 +105,8 @@ pub async fn serve_repository_stored_path(path: PathBuf, display_dir: bool, has_
                     core::mem::swap(&mut start, &mut next);
                 }
 
-                let map = unsafe { memmap2::MmapOptions::new().populate().no_reserve_swap().map_copy_read_only(&file) }?;
+                //Pages are only read, once they are sent, so that large files don't delay the response.
+                let map = unsafe { memmap2::MmapOptions::new().no_reserve_swap().map_copy_read_only(&file) }?;
                 map.advise(memmap2::Advice::Sequential)?;
                 map.advise(memmap2::Advice::WillNeed)?;
                 next = Instant::now();
@@ -95,7 +114,7 @@ pub async fn serve_repository_stored_path(path: PathBuf, display_dir: bool, has_
                 tracing::info!("get_repo_file_impl: {}: get_repo_look_locations: serve_repository_stored_path: memory-map file took {}µs", path.display(), (next-start).as_micros());
                 core::mem::swap(&mut start, &mut next);
 
-                let hash = blake3::Hasher::default().update(&*map).finalize();
+                let hash = crate::hash_cache::hash_file(&file.metadata()?, &map);
                 next = Instant::now();
                 timings.push_iter_nodelim([r#"resolveImplLocalETagFile;dur="#, (next-start).as_server_timing_duration().to_string().as_str(), r#";desc="Resolve Impl: Local: Calculate File ETag""#]);
                 tracing::info!("get_repo_file_impl: {}: get_repo_look_locations: serve_repository_stored_path: calculate file etag took {}µs", path.display(), (next-start).as_micros());
@@ -145,7 +164,7 @@ pub async fn serve_repository_stored_path(path: PathBuf, display_dir: bool, has_
         core::mem::swap(&mut start, &mut next);
 
         let mut file = tokio::fs::File::from_std(file);
-        match FileMetadata::validate(&config, &str_path, &path, &mut data, &mut file, &metadata, &hash).await {
+        match FileMetadata::validate(RepoFile { config: &config, repo: &repo, str_path: &str_path }, &path, &mut data, &mut file, &metadata, &hash).await {
             Ok(_) => {},
             Err(err) => {
                 tracing::error!("Failed to get File Metadata for {str_path}: {err:#?}");
@@ -165,7 +184,7 @@ pub async fn serve_repository_stored_path(path: PathBuf, display_dir: bool, has_
     }
 }
 
-async fn serve_repository_stored_dir(path: &PathBuf) -> Result<HashMap<String, FileType>, Vec<GetRepoFileError>> {
+async fn serve_repository_stored_dir(repo: &str, path: &PathBuf) -> Result<HashMap<String, ListingEntry>, Vec<GetRepoFileError>> {
     match tokio::fs::read_dir(&path).await {
         Err(err) => {
             match err.kind() {
@@ -177,7 +196,7 @@ async fn serve_repository_stored_dir(path: &PathBuf) -> Result<HashMap<String, F
             }
         }
         Ok(mut v) => {
-            let mut out = HashMap::new();
+            let mut out = Vec::new();
             loop {
                 let entry = match v.next_entry().await {
                     Err(err) => {
@@ -197,12 +216,12 @@ async fn serve_repository_stored_dir(path: &PathBuf) -> Result<HashMap<String, F
                     }
                     Ok(v) => v,
                 };
-                if file_name.starts_with(".") && file_name.ends_with(".json") {
+                if file_name.starts_with(".") && (file_name.ends_with(".json") || crate::temp_file::is_temp_file(file_name.as_ref())) {
                     continue;
                 }
-                let file_type = match entry.file_type().await {
+                let metadata = match entry.metadata().await {
                     Err(err) => {
-                        tracing::warn!("Error: failed to get the file-type of the directory entry");
+                        tracing::warn!("Error: failed to get the metadata of the directory entry");
                         if err.kind() == ErrorKind::NotFound {
                             return Err(vec![GetRepoFileError::NotFound]);
                         }
@@ -210,9 +229,76 @@ async fn serve_repository_stored_dir(path: &PathBuf) -> Result<HashMap<String, F
                     }
                     Ok(v) => v,
                 };
-                out.insert(file_name, file_type);
+                out.push((file_name, metadata));
             }
-            Ok(out)
+            let repo = Box::<str>::from(repo);
+            //Looking up the known hashes reads the hash cache.
+            tokio::task::spawn_blocking(move ||out.into_iter().map(|(file_name, metadata)|(file_name, ListingEntry {
+                is_dir: metadata.is_dir(),
+                size: metadata.is_file().then_some(metadata.len()),
+                modified: metadata.modified().ok().map(chrono::DateTime::<chrono::Utc>::from),
+                hash: metadata.is_file().then(||crate::hash_cache::cached_hash(&metadata)).flatten(),
+                repos: vec![repo.clone()],
+            })).collect()).await.map_err(|err|{
+                tracing::error!("Panicked whilst listing directory {}: {err}", path.display());
+                vec![GetRepoFileError::Panicked]
+            })
         }
     }
-}
\ No newline at end of file
+}
+/// Computes the checksum file `path` from its artifact, if it's missing, and stores it next to the artifact.
+/// This way checksums can be verified for files, which were copied into a repository by hand,
+/// or cached from an upstream, which doesn't publish all checksums.
+/// Existing checksum files are never replaced, even if they don't match, because they may have been published that way.
+/// Returns the checksum with the metadata of the artifact, or `None`, if the checksum file exists or there is no artifact.
+/// Storing the file is only a cache, so the checksum is also returned, if that fails (e.g. in a read-only repository).
+#[cfg(feature = "put")]
+async fn virtual_checksum(path: &std::path::Path, extension: &str) -> Result<Option<(std::fs::Metadata, String)>, std::io::Error> {
+    let artifact = path.with_extension("");
+    //Checksums of checksums and of hidden files are never served
+    if artifact.extension().and_then(|v|v.to_str()).is_some_and(|v|CHECKSUM_EXTENSIONS.contains(&v))
+        || artifact.file_name().is_none_or(|v|v.to_string_lossy().starts_with(".")) {
+        return Ok(None);
+    }
+    let metadata = match tokio::fs::metadata(&artifact).await {
+        Ok(v) if v.is_file() => v,
+        Ok(_) => return Ok(None),
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    match tokio::fs::metadata(path).await {
+        Ok(_) => return Ok(None),
+        Err(err) if err.kind() == ErrorKind::NotFound => {},
+        Err(err) => return Err(err),
+    }
+
+    let checksum = crate::put::compute_checksum(artifact, extension).await?;
+    if let Err(err) = write_virtual_checksum(path, &checksum).await {
+        tracing::warn!("Failed to store computed checksum file {}: {err}", path.display());
+    }
+    Ok(Some((metadata, checksum)))
+}
+
+/// Stores a computed checksum file, unless it was deployed or computed by another request meanwhile.
+#[cfg(feature = "put")]
+async fn write_virtual_checksum(path: &std::path::Path, checksum: &str) -> Result<(), std::io::Error> {
+    let temp_path = crate::temp_file::temp_path(path);
+    if let Err(err) = tokio::fs::write(&temp_path, checksum).await {
+        crate::temp_file::remove(&temp_path).await;
+        return Err(err);
+    }
+    match crate::temp_file::persist(&temp_path, path, false).await {
+        Ok(()) => {
+            tracing::info!("Computed missing checksum file {}", path.display());
+            Ok(())
+        },
+        Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+            crate::temp_file::remove(&temp_path).await;
+            Ok(())
+        },
+        Err(err) => {
+            crate::temp_file::remove(&temp_path).await;
+            Err(err)
+        },
+    }
+}
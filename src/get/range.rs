@@ -0,0 +1,196 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+use actix_web::web::Bytes;
+use crate::etag::ETag;
+use crate::RequestHeaders;
+use crate::status::Content;
+
+/// More ranges than this are answered with the whole file, instead of a multipart body made of tiny parts.
+const MAX_RANGES: usize = 32;
+
+/// Serves only the byte ranges of a file requested by the `Range` header (RFC 9110, Section 14).
+/// The ranges are ignored, if they can't be parsed, or if the `If-Range` validator doesn't match the file anymore.
+/// Returns the status, body and content type of the response.
+pub async fn apply_range(
+    request_headers: &RequestHeaders,
+    hash: &blake3::Hash,
+    modification_datetime: Option<chrono::DateTime<chrono::Utc>>,
+    content: Content,
+    content_type: actix_web::http::header::ContentType,
+    header_map: &mut actix_web::http::header::HeaderMap,
+) -> (actix_web::http::StatusCode, Content, actix_web::http::header::ContentType) {
+    header_map.insert(actix_web::http::header::ACCEPT_RANGES, actix_web::http::header::HeaderValue::from_static("bytes"));
+    let unchanged = |content, content_type| (actix_web::http::StatusCode::OK, content, content_type);
+
+    let map = match content {
+        Content::Mmap(map) => map,
+        other => return unchanged(other, content_type),
+    };
+    //Multiple Range headers are invalid, which means that they get ignored.
+    let mut range = request_headers.headers.get_all(actix_web::http::header::RANGE);
+    let range = match (range.next().map(|v|v.to_str()), range.next()) {
+        (Some(Ok(v)), None) => v,
+        _ => return unchanged(Content::Mmap(map), content_type),
+    };
+    if !if_range_matches(request_headers, hash, modification_datetime).await {
+        return unchanged(Content::Mmap(map), content_type);
+    }
+    let len = map.len() as u64;
+    let ranges = match parse_ranges(range, len) {
+        Some(v) => v,
+        None => return unchanged(Content::Mmap(map), content_type),
+    };
+    if ranges.is_empty() {
+        if let Ok(v) = actix_web::http::header::HeaderValue::from_str(&format!("bytes */{len}")) {
+            header_map.insert(actix_web::http::header::CONTENT_RANGE, v);
+        }
+        return (actix_web::http::StatusCode::RANGE_NOT_SATISFIABLE, Content::None, actix_web::http::header::ContentType::plaintext());
+    }
+
+    //Indices fit into usize, because the whole file is memory-mapped
+    let slice = |data: &Bytes, range: &Range<u64>|data.slice(range.start as usize..range.end as usize);
+    if let [range] = ranges.as_slice() {
+        if let Ok(v) = actix_web::http::header::HeaderValue::from_str(&content_range(range, len)) {
+            header_map.insert(actix_web::http::header::CONTENT_RANGE, v);
+        }
+        return (actix_web::http::StatusCode::PARTIAL_CONTENT, Content::Chunks(VecDeque::from([slice(&Bytes::from_owner(map), range)])), content_type);
+    }
+
+    let boundary = format!("byteranges-{}", hash.to_hex());
+    let multipart_type = match format!("multipart/byteranges; boundary={boundary}").parse() {
+        Ok(v) => actix_web::http::header::ContentType(v),
+        Err(err) => {
+            tracing::warn!("Failed to create the multipart/byteranges content type: {err}");
+            return unchanged(Content::Mmap(map), content_type);
+        }
+    };
+    let data = Bytes::from_owner(map);
+    let mut chunks = VecDeque::with_capacity(ranges.len() * 3 + 1);
+    for range in &ranges {
+        chunks.push_back(Bytes::from(format!(
+            "--{boundary}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+            content_type.0,
+            content_range(range, len),
+        )));
+        chunks.push_back(slice(&data, range));
+        chunks.push_back(Bytes::from_static(b"\r\n"));
+    }
+    chunks.push_back(Bytes::from(format!("--{boundary}--\r\n")));
+    (actix_web::http::StatusCode::PARTIAL_CONTENT, Content::Chunks(chunks), multipart_type)
+}
+
+fn content_range(range: &Range<u64>, len: u64) -> String {
+    format!("bytes {}-{}/{len}", range.start, range.end - 1)
+}
+
+/// Without an `If-Range` header, ranges always apply.
+/// Otherwise, the header must contain the strong ETag of the file, or exactly its modification time.
+async fn if_range_matches(request_headers: &RequestHeaders, hash: &blake3::Hash, modification_datetime: Option<chrono::DateTime<chrono::Utc>>) -> bool {
+    let value = match request_headers.headers.get(actix_web::http::header::IF_RANGE) {
+        None => return true,
+        Some(v) => match v.to_str() {
+            Ok(v) => v.trim(),
+            Err(_) => return false,
+        },
+    };
+    if value.starts_with("\"") || value.starts_with("W/") {
+        return match ETag::parse(value) {
+            Some(tag) => !tag.weak && tag.matches(hash).await.unwrap_or(false),
+            None => false,
+        };
+    }
+    match (chrono::DateTime::parse_from_rfc2822(value), modification_datetime) {
+        //A modification time is only a strong validator, if the file wasn't modified within the same second afterward.
+        (Ok(http_time), Some(modified)) => http_time.timestamp() == modified.timestamp() && modified < chrono::Utc::now() - chrono::TimeDelta::seconds(1),
+        _ => false,
+    }
+}
+
+/// Parses a `bytes=` range set into the satisfiable ranges, with an exclusive end.
+/// Returns `None`, if the header should be ignored, and no ranges, if none of them can be satisfied.
+fn parse_ranges(value: &str, len: u64) -> Option<Vec<Range<u64>>> {
+    let (unit, set) = value.split_once('=')?;
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return None;
+    }
+    let mut ranges = Vec::new();
+    let mut count = 0;
+    for spec in set.split(',').map(str::trim).filter(|v|!v.is_empty()) {
+        count += 1;
+        if count > MAX_RANGES {
+            return None;
+        }
+        let (first, last) = spec.split_once('-')?;
+        let range = if first.is_empty() {
+            let suffix = last.parse::<u64>().ok()?;
+            len.saturating_sub(suffix)..len
+        } else {
+            let first = first.parse::<u64>().ok()?;
+            let last = if last.is_empty() {
+                len
+            } else {
+                let last = last.parse::<u64>().ok()?;
+                if last < first {
+                    return None;
+                }
+                last.saturating_add(1).min(len)
+            };
+            first..last
+        };
+        if range.start < range.end {
+            ranges.push(range);
+        }
+    }
+    if count == 0 {
+        return None;
+    }
+    Some(ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_ranges, MAX_RANGES};
+
+    /// The parsed ranges as `(start, end)`, which are easier to compare.
+    fn ranges(value: &str, len: u64) -> Option<Vec<(u64, u64)>> {
+        parse_ranges(value, len).map(|v|v.into_iter().map(|v|(v.start, v.end)).collect())
+    }
+
+    #[test]
+    fn single_ranges_are_clamped_to_the_file() {
+        assert_eq!(ranges("bytes=0-99", 1000), Some(vec![(0, 100)]));
+        assert_eq!(ranges("bytes=900-", 1000), Some(vec![(900, 1000)]));
+        assert_eq!(ranges("bytes=900-2000", 1000), Some(vec![(900, 1000)]));
+        assert_eq!(ranges("bytes=-100", 1000), Some(vec![(900, 1000)]));
+        assert_eq!(ranges("bytes=-2000", 1000), Some(vec![(0, 1000)]));
+    }
+
+    #[test]
+    fn multiple_ranges_keep_their_order() {
+        assert_eq!(ranges("bytes=500-599, 0-99,-10", 1000), Some(vec![(500, 600), (0, 100), (990, 1000)]));
+    }
+
+    #[test]
+    fn unsatisfiable_ranges_are_left_out() {
+        assert_eq!(ranges("bytes=1000-", 1000), Some(vec![]));
+        assert_eq!(ranges("bytes=-0", 1000), Some(vec![]));
+        assert_eq!(ranges("bytes=2000-3000, 0-0", 1000), Some(vec![(0, 1)]));
+    }
+
+    #[test]
+    fn invalid_headers_are_ignored() {
+        assert_eq!(ranges("items=0-99", 1000), None);
+        assert_eq!(ranges("bytes=", 1000), None);
+        assert_eq!(ranges("bytes=99-0", 1000), None);
+        assert_eq!(ranges("bytes=a-b", 1000), None);
+        assert_eq!(ranges("bytes=100", 1000), None);
+    }
+
+    #[test]
+    fn too_many_ranges_are_ignored() {
+        let set = vec!["0-0"; MAX_RANGES + 1].join(",");
+        assert_eq!(ranges(&format!("bytes={set}"), 1000), None);
+        let set = vec!["0-0"; MAX_RANGES].join(",");
+        assert_eq!(ranges(&format!("bytes={set}"), 1000).map(|v|v.len()), Some(MAX_RANGES));
+    }
+}
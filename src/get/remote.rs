@@ -6,19 +6,21 @@ use reqwest::StatusCode;
 use tokio::io::{AsyncSeekExt, AsyncWriteExt};
 use tokio::time::Instant;
 use crate::err::GetRepoFileError;
+use crate::events::ChangeKind;
 use crate::file_metadata::FileMetadata;
 use crate::get::StoredRepoPath;
 use crate::remote::get_remote_request;
 use crate::repository::{RemoteUpstream, Repository};
 use crate::server_timings::AsServerTimingDuration;
+use crate::temp_file;
 use crate::timings::ServerTimings;
 
 pub async fn serve_remote_repository(
     remote: RemoteUpstream,
     str_path: Arc<str>,
-    repo: &str,
+    repo: Arc<str>,
     path: Arc<Path>,
-    config: &Repository,
+    config: Arc<Repository>,
     request_url: Arc<str>,
     remote_client: Option<IpAddr>,
 ) -> Result<StoredRepoPath, Vec<GetRepoFileError>> {
@@ -68,10 +70,12 @@ pub async fn serve_remote_repository(
         timings.push_iter_nodelim([r#"resolveImplRemoteFSCreateDirAll;dur="#, (next-start).as_server_timing_duration().to_string().as_str(), r#";desc="Resolve Impl: Remote: Create All Local Dirs""#]);
         core::mem::swap(&mut start, &mut next);
 
-        let (path, file, mut timings, mut start) = match tokio::task::spawn_blocking(move ||{
+        //The file is only moved to its final location, once it's completely received, so that it's never served truncated.
+        let temp_path = temp_file::temp_path(&path);
+        let (temp_path, file, mut timings, mut start) = match tokio::task::spawn_blocking(move ||{
             let mut start = start;
             let mut next;
-            let file = std::fs::File::create_new(&path)?;
+            let file = std::fs::File::create_new(&temp_path)?;
 
             next = Instant::now();
             timings.push_iter_nodelim([r#"resolveImplRemoteFSCreateFile;dur="#, (next-start).as_server_timing_duration().to_string().as_str(), r#";desc="Resolve Impl: Remote: Create new Local File""#]);
@@ -84,7 +88,7 @@ pub async fn serve_remote_repository(
             timings.push_iter_nodelim([r#"resolveImplRemoteFSCreateFile;dur="#, (next-start).as_server_timing_duration().to_string().as_str(), r#";desc="Resolve Impl: Remote: Lock Local File Exclusively""#]);
             core::mem::swap(&mut start, &mut next);
 
-            Ok::<_, std::io::Error>((path, file, timings, start))
+            Ok::<_, std::io::Error>((temp_path, file, timings, start))
         }).await {
             Ok(Ok(v)) => v,
             Ok(Err(v)) => {
@@ -110,6 +114,7 @@ pub async fn serve_remote_repository(
             let body = match response.chunk().await {
                 Err(err) => {
                     tracing::warn!("Error contacting Upstream repo: {err}");
+                    temp_file::remove(&temp_path).await;
                     return Err(vec![GetRepoFileError::UpstreamBodyReadError]);
                 }
                 Ok(Some(v)) => v,
@@ -117,6 +122,7 @@ pub async fn serve_remote_repository(
             };
             current_size += body.len() as u64;
             if current_size >= config.max_file_size.unwrap_or(crate::DEFAULT_MAX_FILE_SIZE) {
+                temp_file::remove(&temp_path).await;
                 return Err(vec![GetRepoFileError::UpstreamFileTooLarge])
             }
             hash.update(&*body);
@@ -124,13 +130,8 @@ pub async fn serve_remote_repository(
             match file.write_all(&*body).await {
                 Ok(()) => {},
                 Err(err) => {
-                    tracing::error!("Error writing to File {}: {err}", path.display());
-                    match tokio::fs::remove_file(&path).await {
-                        Ok(()) => {},
-                        Err(err) => {
-                            tracing::error!("Error deleting File after error writing to File {}: {err}", path.display());
-                        }
-                    }
+                    tracing::error!("Error writing to File {}: {err}", temp_path.display());
+                    temp_file::remove(&temp_path).await;
                     return Err(vec![GetRepoFileError::FileWriteFailed]);
                 }
             }
@@ -139,13 +140,8 @@ pub async fn serve_remote_repository(
         match file.shutdown().await  {
             Ok(()) => {},
             Err(err) => {
-                tracing::error!("Error flushing File {}: {err}", path.display());
-                match tokio::fs::remove_file(&path).await {
-                    Ok(()) => {},
-                    Err(err) => {
-                        tracing::error!("Error deleting File after error flushing File {}: {err}", path.display());
-                    }
-                }
+                tracing::error!("Error flushing File {}: {err}", temp_path.display());
+                temp_file::remove(&temp_path).await;
                 return Err(vec![GetRepoFileError::FileFlushFailed]);
             }
         }
@@ -153,7 +149,8 @@ pub async fn serve_remote_repository(
         match file.seek(SeekFrom::Start(0)).await  {
             Ok(_) => {},
             Err(err) => {
-                tracing::error!("Error seeking File {}: {err}", path.display());
+                tracing::error!("Error seeking File {}: {err}", temp_path.display());
+                temp_file::remove(&temp_path).await;
                 return Err(vec![GetRepoFileError::FileSeekFailed]);
             }
         }
@@ -171,6 +168,18 @@ pub async fn serve_remote_repository(
         timings.push_iter_nodelim([r#"resolveImplRemoteMetadataWrite;dur="#, (next-start).as_server_timing_duration().to_string().as_str(), r#";desc="Resolve Impl: Remote: Write File Metadata Info""#]);
         core::mem::swap(&mut start, &mut next);
 
+        match temp_file::persist(&temp_path, &path, true).await {
+            Ok(()) => crate::events::publish(&repo, ChangeKind::Cached, Path::new(&*str_path)),
+            Err(err) => {
+                tracing::error!("Error moving File {} to {}: {err}", temp_path.display(), path.display());
+                temp_file::remove(&temp_path).await;
+                return Err(vec![GetRepoFileError::FileWriteFailed]);
+            }
+        }
+        next = Instant::now();
+        timings.push_iter_nodelim([r#"resolveImplRemoteFSPersist;dur="#, (next-start).as_server_timing_duration().to_string().as_str(), r#";desc="Resolve Impl: Remote: Move completed File to final Location""#]);
+        core::mem::swap(&mut start, &mut next);
+
         let file = file.into_std().await;
         let (metadata, map) = match tokio::task::spawn_blocking(move ||{
             #[cfg(feature = "locking")]
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+
+/// Directory next to the repository configs, which holds one small file per hashed inode.
+/// Entries of deleted files are left behind, but they are tiny and get replaced, once the inode is reused.
+const HASH_CACHE_DIR: &str = ".hashes";
+/// The in-memory cache is dropped, once it has this many entries.
+const MEMORY_ENTRIES: usize = 16384;
+
+/// Identifies the content of a file: any write changes the size or one of the times.
+#[cfg_attr(not(unix), allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde_derive::Serialize, serde_derive::Deserialize)]
+struct Stamp {
+    size: u64,
+    modified_secs: i64,
+    modified_nanos: i64,
+    changed_secs: i64,
+    changed_nanos: i64,
+}
+
+#[cfg_attr(not(unix), allow(dead_code))]
+#[derive(Debug, serde_derive::Serialize, serde_derive::Deserialize)]
+struct Entry {
+    #[serde(flatten)]
+    stamp: Stamp,
+    hash: String,
+}
+
+/// Hashes by device and inode number
+type HashStore = HashMap<(u64, u64), (Stamp, blake3::Hash)>;
+#[cfg_attr(not(unix), allow(dead_code))]
+static MEMORY: LazyLock<Mutex<HashStore>> = LazyLock::new(Default::default);
+
+/// Returns the blake3 hash of a file, which is used as its ETag.
+/// The hash is only computed from `data`, if the file changed since it was last hashed,
+/// so that serving a large file doesn't need to read all of it first.
+/// This does blocking IO.
+pub fn hash_file(metadata: &std::fs::Metadata, data: &[u8]) -> blake3::Hash {
+    if let Some(hash) = cached_hash(metadata) {
+        return hash;
+    }
+    let hash = blake3::Hasher::new().update(data).finalize();
+    #[cfg(unix)]
+    {
+        let (key, stamp) = key(metadata);
+        write_entry(key, &Entry { stamp, hash: hash.to_hex().to_string() });
+        remember(key, stamp, hash);
+    }
+    hash
+}
+
+/// Returns the hash of a file, if it was hashed since it last changed.
+/// This does blocking IO.
+#[cfg(unix)]
+pub fn cached_hash(metadata: &std::fs::Metadata) -> Option<blake3::Hash> {
+    let (key, stamp) = key(metadata);
+    let memory = MEMORY.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+    if let Some((cached_stamp, hash)) = memory.get(&key)
+        && *cached_stamp == stamp {
+        return Some(*hash);
+    }
+    drop(memory);
+
+    let hash = match read_entry(key) {
+        Some(entry) if entry.stamp == stamp => blake3::Hash::from_hex(&entry.hash).ok()?,
+        _ => return None,
+    };
+    remember(key, stamp, hash);
+    Some(hash)
+}
+
+/// Without inode numbers, there is no reliable key, so files are always hashed.
+#[cfg(not(unix))]
+pub fn cached_hash(_: &std::fs::Metadata) -> Option<blake3::Hash> {
+    None
+}
+
+#[cfg(unix)]
+fn key(metadata: &std::fs::Metadata) -> ((u64, u64), Stamp) {
+    use std::os::unix::fs::MetadataExt;
+    let stamp = Stamp {
+        size: metadata.size(),
+        modified_secs: metadata.mtime(),
+        modified_nanos: metadata.mtime_nsec(),
+        changed_secs: metadata.ctime(),
+        changed_nanos: metadata.ctime_nsec(),
+    };
+    ((metadata.dev(), metadata.ino()), stamp)
+}
+
+#[cfg(unix)]
+fn remember(key: (u64, u64), stamp: Stamp, hash: blake3::Hash) {
+    let mut memory = MEMORY.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+    if memory.len() >= MEMORY_ENTRIES {
+        memory.clear();
+    }
+    memory.insert(key, (stamp, hash));
+}
+
+fn entry_path((dev, ino): (u64, u64)) -> PathBuf {
+    PathBuf::from(HASH_CACHE_DIR).join(format!("{dev:x}-{ino:x}.json"))
+}
+
+#[cfg_attr(not(unix), allow(dead_code))]
+fn read_entry(key: (u64, u64)) -> Option<Entry> {
+    let path = entry_path(key);
+    match std::fs::read(&path) {
+        Ok(v) => match serde_json::from_slice(&v) {
+            Ok(v) => Some(v),
+            Err(err) => {
+                tracing::warn!("Ignoring invalid hash cache entry {}: {err}", path.display());
+                None
+            }
+        },
+        Err(err) if err.kind() == ErrorKind::NotFound => None,
+        Err(err) => {
+            tracing::warn!("Failed to read hash cache entry {}: {err}", path.display());
+            None
+        }
+    }
+}
+
+/// Failing to write an entry only means, that the file gets hashed again after a restart.
+#[cfg_attr(not(unix), allow(dead_code))]
+fn write_entry(key: (u64, u64), entry: &Entry) {
+    let path = entry_path(key);
+    let json = match serde_json::to_vec(entry) {
+        Ok(v) => v,
+        Err(err) => {
+            tracing::error!("Failed to serialize hash cache entry {}: {err}", path.display());
+            return;
+        }
+    };
+    let temp = crate::temp_file::temp_path(&path);
+    let result = match std::fs::write(&temp, &json) {
+        Err(err) if err.kind() == ErrorKind::NotFound => std::fs::create_dir_all(HASH_CACHE_DIR)
+            .and_then(|()|std::fs::write(&temp, &json)),
+        other => other,
+    }.and_then(|()|std::fs::rename(&temp, &path));
+    if let Err(err) = result {
+        tracing::warn!("Failed to write hash cache entry {}: {err}", path.display());
+        let _ = std::fs::remove_file(&temp);
+    }
+}
@@ -0,0 +1,137 @@
+#![cfg(feature = "put")]
+use std::net::IpAddr;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use crate::audit::{Actor, FileSummary};
+use crate::auth::BasicAuthentication;
+use crate::err::GetRepoFileError;
+use crate::repository::{get_repo, get_repo_config, lock_staging_repo, Repository};
+use crate::status::{Content, Return};
+
+/// A request, which modifies a hosted repository (PUT, DELETE or POST).
+pub struct HostedRequest {
+    /// Requests to a group repository are forwarded to its hosted deploy target, so this is the target's name.
+    pub repo: Arc<str>,
+    pub config: Arc<Repository>,
+    /// The requested path, relative to the repository
+    pub path: PathBuf,
+    /// `path` without leading or trailing slashes, which authorization is checked against
+    pub str_path: String,
+    pub auth: Option<BasicAuthentication>,
+    /// Held for the whole request, if `repo` is a staging repository, so that it doesn't get promoted or dropped meanwhile.
+    pub staging_guard: Option<tokio::sync::OwnedRwLockReadGuard<bool>>,
+}
+
+/// Splits the request path into the repository and the path within it, and checks, that the path is safe to use.
+/// Repositories with upstreams are rejected with `remotes_forbidden`, because only hosted repositories can be modified.
+/// Rejected requests are recorded in the audit log as attempts to `action`.
+pub async fn parse_hosted_request(req: &actix_web::HttpRequest, auth: Result<BasicAuthentication, Return>, client_ip: Option<IpAddr>, action: &str, remotes_forbidden: &'static str) -> Result<HostedRequest, Return> {
+    let uri = req.full_url();
+    let path = uri.path();
+    let path = path.strip_prefix("/").unwrap_or(path);
+    let path = PathBuf::from(path);
+    let repo: Arc<str>;
+    let path = {
+        let mut iter = path.components();
+        loop {
+            match iter.next() {
+                Some(Component::Normal(v)) => {
+                    match v.to_str() {
+                        Some(v) => {
+                            repo = Arc::from(v);
+                            break;
+                        },
+                        None => return Err(reject(None, &path, client_ip, action, Return{
+                            status: actix_web::http::StatusCode::BAD_REQUEST,
+                            content: Content::Str("A part of the request was not valid UTF-8"),
+                            content_type: actix_web::http::header::ContentType::plaintext(),
+                            header_map: None,
+                        }).await),
+                    }
+                }
+                Some(_) => continue,
+                None => return Err(reject(None, &path, client_ip, action, Return{
+                    status: actix_web::http::StatusCode::NOT_FOUND,
+                    content: Content::None,
+                    content_type: actix_web::http::header::ContentType::plaintext(),
+                    header_map: None,
+                }).await),
+            }
+        }
+        PathBuf::from_iter(iter)
+    };
+    let auth = match auth {
+        Err(err) if err.status == actix_web::http::StatusCode::FORBIDDEN => None,
+        Err(err) => return Err(reject(Some(&repo), &path, client_ip, action, err).await),
+        Ok(v) => Some(v),
+    };
+    if path.components().any(|v|matches!(v, Component::ParentDir | Component::RootDir | Component::Prefix(_))) {
+        return Err(reject(Some(&repo), &path, client_ip, action, GetRepoFileError::BadRequestPath.to_return()).await);
+    }
+    if path.has_root() {
+        return Err(reject(Some(&repo), &path, client_ip, action, GetRepoFileError::BadRequestPath.to_return()).await);
+    }
+    let str_path = match path.to_str() {
+        None => return Err(reject(Some(&repo), &path, client_ip, action, GetRepoFileError::InvalidUTF8.to_return()).await),
+        Some(v) => v,
+    };
+    let str_path = str_path.strip_prefix("/").unwrap_or(str_path);
+    let str_path = str_path.strip_suffix("/").unwrap_or(str_path).to_owned();
+
+    let config = match get_repo_config(&repo) {
+        Ok(v) => v,
+        Err(err) => return Err(reject(Some(&repo), &path, client_ip, action, err.to_return()).await),
+    };
+    //Requests to a group repository are forwarded to its hosted deploy target.
+    let (repo, config) = match config.get_deploy_target() {
+        Some(target) => match get_repo_config(target) {
+            Ok(v) => (Arc::from(target), v),
+            Err(err) => return Err(reject(Some(&repo), &path, client_ip, action, err.to_return()).await),
+        },
+        None => (repo, config),
+    };
+
+    if !config.upstreams.is_empty() {
+        return Err(reject(Some(&repo), &path, client_ip, action, Return {
+            status: actix_web::http::StatusCode::FORBIDDEN,
+            content: Content::Str(remotes_forbidden),
+            content_type: actix_web::http::header::ContentType::plaintext(),
+            header_map: None,
+        }).await)
+    }
+
+    let staging_guard = match config.staging_target {
+        Some(_) => match lock_staging_repo(&repo).await {
+            Some(v) => Some(v),
+            None => return Err(reject(Some(&repo), &path, client_ip, action, GetRepoFileError::NotFound.to_return()).await),
+        },
+        None => None,
+    };
+
+    Ok(HostedRequest {
+        repo,
+        config,
+        path,
+        str_path,
+        auth,
+        staging_guard,
+    })
+}
+
+/// Records a request, which was rejected before it could authenticate, as an unauthenticated attempt.
+/// Requests, which don't name an existing repository, are recorded in [`crate::audit::UNKNOWN_REPOSITORY`] with the whole path.
+async fn reject(repo: Option<&str>, path: &Path, client_ip: Option<IpAddr>, action: &str, ret: Return) -> Return {
+    let actor = Actor::new(client_ip);
+    match repo {
+        Some(repo) if get_repo(repo).is_some() => crate::audit::record(repo, &actor, action, path, &FileSummary::default(), ret.status).await,
+        Some(repo) => {
+            let path = match path.as_os_str().is_empty() {
+                true => PathBuf::from(repo),
+                false => Path::new(repo).join(path),
+            };
+            crate::audit::record(crate::audit::UNKNOWN_REPOSITORY, &actor, action, &path, &FileSummary::default(), ret.status).await
+        },
+        None => crate::audit::record(crate::audit::UNKNOWN_REPOSITORY, &actor, action, path, &FileSummary::default(), ret.status).await,
+    }
+    ret
+}
@@ -3,7 +3,7 @@ extern crate core;
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::os::fd::AsRawFd;
-use std::sync::LazyLock;
+use std::sync::{Arc, LazyLock, RwLock};
 use std::time::{Duration};
 use actix_web::dev::Payload;
 use actix_web::HttpRequest;
@@ -20,7 +20,32 @@ mod auth;
 mod err;
 #[cfg(feature = "put")]
 mod put;
+#[cfg(feature = "put")]
+mod delete;
+#[cfg(feature = "put")]
+mod hosted_request;
+#[cfg(feature = "put")]
+mod snapshot_retention;
+#[cfg(feature = "put")]
+mod pom;
+#[cfg(feature = "put")]
+mod request_digest;
+#[cfg(feature = "put")]
+mod checksum_deploy;
+#[cfg(feature = "put")]
+mod post;
+#[cfg(feature = "put")]
+mod bundle;
+#[cfg(feature = "put")]
+mod staging;
+#[cfg(feature = "put")]
+mod transfer;
+#[cfg(feature = "put")]
+mod audit;
+#[cfg(feature = "put")]
+mod webhook;
 mod maven_metadata;
+mod maven_version;
 mod path_info;
 mod etag;
 mod server_timings;
@@ -30,6 +55,9 @@ mod remote;
 #[allow(dead_code)]
 mod file_ext;
 mod timings;
+mod temp_file;
+mod events;
+mod hash_cache;
 
 static UNAUTHORIZED: fn() -> Return = ||Return{
     status: actix_web::http::StatusCode::UNAUTHORIZED,
@@ -62,11 +90,22 @@ static MAIN_CONFIG:LazyLock<Repository> = LazyLock::new(||{
     let config = private::read_main_config().expect("Failed to read main configuration");
     config
 });
-type RepositoryStore = HashMap<Box<str>, Repository>;
+type RepositoryStore = HashMap<Arc<str>, Arc<Repository>>;
 static REPOSITORIES:LazyLock<RepositoryStore> = LazyLock::new(|| {
-    let hm = gather_repos(&MAIN_CONFIG).expect("Failed to read repo configurations");
-    hm
+    let mut hm = gather_repos(&MAIN_CONFIG).expect("Failed to read repo configurations");
+    //Staging repositories only record their target, the rest of their config is derived from it.
+    let staging = hm.extract_if(|_, v|v.staging_target.is_some()).collect::<Vec<_>>();
+    for (name, config) in staging {
+        let target = config.staging_target.as_deref().unwrap_or_default();
+        match hm.get(target) {
+            Some(v) => repository::register_staging_repo(Arc::from(name), Arc::new(v.staging_config(target))),
+            None => tracing::error!("Ignoring staging repository '{name}', because its target '{target}' doesn't exist"),
+        }
+    }
+    hm.into_iter().map(|(k, v)|(Arc::from(k), Arc::new(v))).collect()
 });
+type StagingRepositoryStore = HashMap<Arc<str>, repository::StagingRepository>;
+static STAGING_REPOSITORIES:LazyLock<RwLock<StagingRepositoryStore>> = LazyLock::new(Default::default);
 mod private {
     use std::io::SeekFrom;
     use crate::repository::Repository;
@@ -117,6 +156,18 @@ fn main() -> anyhow::Result<()>{
         let _ = LazyLock::force(&MAIN_CONFIG);
         let _ = LazyLock::force(&REPOSITORIES);
     }
+    let repos = REPOSITORIES.keys()
+        .cloned()
+        .chain(STAGING_REPOSITORIES.read().unwrap_or_else(std::sync::PoisonError::into_inner).keys().cloned())
+        .collect::<Vec<_>>();
+    for repo in repos {
+        match temp_file::cleanup(std::path::Path::new(&*repo)) {
+            Ok(0) => {},
+            Ok(removed) => tracing::warn!("{repo}: Removed {removed} leftover temporary files from interrupted writes"),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {},
+            Err(err) => tracing::error!("{repo}: Failed to remove leftover temporary files: {err}"),
+        }
+    }
 
     async_main()
 }
@@ -205,6 +256,10 @@ async fn async_main() -> anyhow::Result<()> {
             .wrap(actix_web::middleware::NormalizePath::new(actix_web::middleware::TrailingSlash::MergeOnly))
             .default_service(actix_web::web::route().to(repo_file))
     );
+    #[cfg(feature = "put")]
+    snapshot_retention::spawn_scheduled_pruning();
+    #[cfg(feature = "put")]
+    checksum_deploy::spawn_index_build();
 
 
 
@@ -281,7 +336,11 @@ async fn repo_file(
 ) -> Return {
     match method {
         #[cfg(feature = "put")]
-        actix_web::http::Method::PUT => put::put_repo_file(req, auth, data).await,
+        actix_web::http::Method::PUT => put::put_repo_file(req, auth, request_headers.client_ip, data).await,
+        #[cfg(feature = "put")]
+        actix_web::http::Method::DELETE => delete::delete_repo_file(req, auth, request_headers.client_ip).await,
+        #[cfg(feature = "put")]
+        actix_web::http::Method::POST => post::post_repo_file(req, auth, request_headers.client_ip, data).await,
         actix_web::http::Method::GET |
         actix_web::http::Method::HEAD
             => get::get_repo_file(req, auth, request_headers).await,
@@ -1,31 +1,124 @@
 use std::collections::HashSet;
+use crate::maven_version::ComparableVersion;
 
 #[derive(Debug, serde_derive::Deserialize, serde_derive::Serialize)]
+#[serde(rename="metadata", rename_all="camelCase")]
 pub struct MavenMetadata {
     pub group_id: String,
     pub artifact_id: String,
+    /// Only present in the version-level metadata of snapshots
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
     pub versioning: Versioning,
 }
+impl MavenMetadata {
+    /// Adds the versions and snapshot builds, which another repository lists for the same artifact.
+    pub fn merge(&mut self, other: MavenMetadata) {
+        if self.version.is_none() {
+            self.version = other.version;
+        }
+        self.versioning.merge(other.versioning);
+    }
+}
 #[derive(Debug, serde_derive::Deserialize, serde_derive::Serialize)]
 #[serde(rename_all="camelCase")]
 pub struct Versioning {
-    pub latest: String,
-    pub release: String,
-    #[serde(default)]
+    /// Absent, if no versions are listed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest: Option<String>,
+    /// Absent, if only SNAPSHOT versions are listed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub versions: Option<Versions>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub snapshot: Option<Snapshot>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub snapshot_versions: Option<SnapshotVersions>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub last_updated: Option<String>, 
 }
 
+impl Versioning {
+    fn merge(&mut self, other: Versioning) {
+        if let Some(versions) = other.versions {
+            self.versions.get_or_insert_default().version.extend(versions.version);
+        }
+        //Without listed versions, the newest latest and release of both are kept.
+        self.latest = max_version(self.latest.take(), other.latest);
+        self.release = max_version(self.release.take(), other.release);
+        self.update_latest_release();
+
+        self.snapshot = self.snapshot.take().max(other.snapshot);
+        if let Some(snapshot_versions) = other.snapshot_versions {
+            let own = &mut self.snapshot_versions.get_or_insert_default().snapshot_version;
+            for snapshot_version in snapshot_versions.snapshot_version {
+                //The same file of a build is listed once, with the newest update time.
+                let existing = own.iter().find(|v|
+                    v.value == snapshot_version.value &&
+                        v.extension == snapshot_version.extension &&
+                        v.classifier == snapshot_version.classifier
+                ).cloned();
+                match existing {
+                    Some(existing) if existing.updated >= snapshot_version.updated => {},
+                    Some(existing) => {
+                        own.remove(&existing);
+                        own.insert(snapshot_version);
+                    },
+                    None => { own.insert(snapshot_version); },
+                }
+            }
+        }
+        //lastUpdated is formatted as yyyyMMddHHmmss, so the newest one sorts last.
+        self.last_updated = self.last_updated.take().max(other.last_updated);
+    }
+
+    /// Recomputes `latest` (any version) and `release` (non-SNAPSHOT) from the listed `versions`, using Maven's version ordering.
+    /// Returns true, if either of them changed.
+    pub fn update_latest_release(&mut self) -> bool {
+        let versions = match &self.versions {
+            Some(v) => v,
+            None => return false,
+        };
+        let latest = versions.version.iter().max_by_key(|v|ComparableVersion::new(v)).cloned();
+        let release = versions.version.iter()
+            .filter(|v|!v.ends_with("-SNAPSHOT"))
+            .max_by_key(|v|ComparableVersion::new(v))
+            .cloned();
+        let changed = latest != self.latest || release != self.release;
+        self.latest = latest;
+        self.release = release;
+        changed
+    }
+
+    /// Returns the entry of the newest build, which has a file with the given classifier and extension.
+    pub fn latest_snapshot_version(&self, classifier: Option<&str>, extension: Option<&str>) -> Option<&SnapshotVersion> {
+        self.snapshot_versions.as_ref()?
+            .snapshot_version
+            .iter()
+            .filter(|v|v.classifier.as_deref() == classifier && v.extension.as_deref() == extension)
+            .max_by_key(|v|v.build_number())
+    }
+}
+
+fn max_version(a: Option<String>, b: Option<String>) -> Option<String> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if ComparableVersion::new(&b) > ComparableVersion::new(&a) { b } else { a }),
+        (a, b) => a.or(b),
+    }
+}
+
 #[derive(Debug, Clone, Default, serde_derive::Deserialize, serde_derive::Serialize)]
 pub struct Versions {
-    #[serde(default)]
+    #[serde(default, serialize_with = "serialize_sorted_versions")]
     pub version: HashSet<String>,
 }
+/// Maven expects the versions to be listed in ascending order.
+fn serialize_sorted_versions<S: serde::Serializer>(versions: &HashSet<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut versions = versions.iter().collect::<Vec<_>>();
+    versions.sort_by_cached_key(|v|ComparableVersion::new(v));
+    serde::Serialize::serialize(&versions, serializer)
+}
 #[derive(Debug, Clone, serde_derive::Deserialize, serde_derive::Serialize, Ord, PartialOrd, Eq, PartialEq, Hash)]
 #[serde(rename_all="camelCase")]
 pub struct Snapshot {
@@ -35,15 +128,87 @@ pub struct Snapshot {
 #[derive(Debug, Clone, Default, serde_derive::Deserialize, serde_derive::Serialize)]
 #[serde(rename_all="camelCase")]
 pub struct SnapshotVersions {
-    #[serde(default)]
+    #[serde(default, serialize_with = "serialize_sorted_snapshot_versions")]
     pub snapshot_version: HashSet<SnapshotVersion>,
 }
+/// Sorted, so that the same entries always serialize to the same document.
+fn serialize_sorted_snapshot_versions<S: serde::Serializer>(snapshot_versions: &HashSet<SnapshotVersion>, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut snapshot_versions = snapshot_versions.iter().collect::<Vec<_>>();
+    snapshot_versions.sort();
+    serde::Serialize::serialize(&snapshot_versions, serializer)
+}
 #[derive(Debug, Clone, serde_derive::Deserialize, serde_derive::Serialize, Ord, PartialOrd, Eq, PartialEq, Hash)]
 #[serde(rename_all="camelCase")]
 pub struct SnapshotVersion {
     pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub extension: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub classifier: Option<String>,
     pub updated: String,
-}
\ No newline at end of file
+}
+impl SnapshotVersion {
+    /// Returns the build-number at the end of the value (e.g. `1.0-20240101.120000-1`).
+    pub fn build_number(&self) -> Option<u64> {
+        self.value.rsplit_once("-").and_then(|(_, v)|v.parse().ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MavenMetadata, Versioning};
+
+    fn metadata(versions: &[&str]) -> MavenMetadata {
+        let mut metadata = MavenMetadata {
+            group_id: "com.example".to_owned(),
+            artifact_id: "foo".to_owned(),
+            version: None,
+            versioning: Versioning {
+                latest: None,
+                release: None,
+                versions: Some(Default::default()),
+                snapshot: None,
+                snapshot_versions: None,
+                last_updated: None,
+            },
+        };
+        metadata.versioning.versions.get_or_insert_default().version.extend(versions.iter().map(|v|v.to_string()));
+        metadata.versioning.update_latest_release();
+        metadata
+    }
+
+    #[test]
+    fn release_is_omitted_without_release_versions() {
+        let xml = quick_xml::se::to_string(&metadata(&["1.0-SNAPSHOT"])).expect("serializable");
+        assert!(xml.contains("<latest>1.0-SNAPSHOT</latest>"));
+        assert!(!xml.contains("<release>"));
+    }
+
+    #[test]
+    fn latest_and_release_are_omitted_without_versions() {
+        let xml = quick_xml::se::to_string(&metadata(&[])).expect("serializable");
+        assert!(!xml.contains("<latest>"));
+        assert!(!xml.contains("<release>"));
+    }
+
+    #[test]
+    fn merge_keeps_the_newest_latest_and_release() {
+        let mut own = metadata(&[]);
+        own.versioning.versions = None;
+        own.versioning.release = Some("1.0".to_owned());
+        let mut other = metadata(&[]);
+        other.versioning.versions = None;
+        other.versioning.latest = Some("2.0-SNAPSHOT".to_owned());
+        other.versioning.release = Some("0.9".to_owned());
+        own.merge(other);
+        assert_eq!(own.versioning.latest.as_deref(), Some("2.0-SNAPSHOT"));
+        assert_eq!(own.versioning.release.as_deref(), Some("1.0"));
+    }
+
+    #[test]
+    fn missing_latest_and_release_are_parsed() {
+        let metadata: MavenMetadata = quick_xml::de::from_str("<metadata><groupId>com.example</groupId><artifactId>foo</artifactId><versioning></versioning></metadata>").expect("parsable");
+        assert_eq!(metadata.versioning.latest, None);
+        assert_eq!(metadata.versioning.release, None);
+    }
+}
@@ -0,0 +1,264 @@
+//! An implementation of Maven's `ComparableVersion` ordering.
+//! See https://maven.apache.org/pom.html#version-order-specification
+
+use std::cmp::Ordering;
+
+const QUALIFIERS: [&str; 7] = ["alpha", "beta", "milestone", "rc", "snapshot", "", "sp"];
+
+#[derive(Debug, Clone)]
+enum Item {
+    /// A number without leading zeros. Zero is the empty string.
+    /// Numbers are kept as strings, so that arbitrarily large numbers can be compared.
+    Int(String),
+    Str(String),
+    List(Vec<Item>),
+}
+
+impl Item {
+    fn int(value: &str) -> Self {
+        Self::Int(value.trim_start_matches('0').to_owned())
+    }
+    fn string(value: &str, followed_by_digit: bool) -> Self {
+        let value = if followed_by_digit && value.len() == 1 {
+            match value {
+                "a" => "alpha",
+                "b" => "beta",
+                "m" => "milestone",
+                v => v,
+            }
+        } else {
+            value
+        };
+        let value = match value {
+            "ga" | "final" | "release" => "",
+            "cr" => "rc",
+            v => v,
+        };
+        Self::Str(value.to_owned())
+    }
+    fn parse(is_digit: bool, value: &str) -> Self {
+        if is_digit {
+            Self::int(value)
+        } else {
+            Self::string(value, false)
+        }
+    }
+    fn is_null(&self) -> bool {
+        match self {
+            Self::Int(v) => v.is_empty(),
+            Self::Str(v) => comparable_qualifier(v) == comparable_qualifier(""),
+            Self::List(v) => v.is_empty(),
+        }
+    }
+
+    /// Compares against a missing item (e.g. `1.0` vs `1.0.1`, when comparing the third item).
+    fn cmp_null(&self) -> Ordering {
+        match self {
+            Self::Int(v) => if v.is_empty() { Ordering::Equal } else { Ordering::Greater },
+            Self::Str(v) => comparable_qualifier(v).cmp(&comparable_qualifier("")),
+            Self::List(v) => match v.first() {
+                None => Ordering::Equal,
+                Some(v) => v.cmp_null(),
+            },
+        }
+    }
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => a.len().cmp(&b.len()).then_with(||a.cmp(b)),
+            (Self::Int(_), _) => Ordering::Greater,
+            (Self::Str(_), Self::Int(_)) => Ordering::Less,
+            (Self::Str(a), Self::Str(b)) => comparable_qualifier(a).cmp(&comparable_qualifier(b)),
+            (Self::Str(_), Self::List(_)) => Ordering::Less,
+            (Self::List(_), Self::Int(_)) => Ordering::Less,
+            (Self::List(_), Self::Str(_)) => Ordering::Greater,
+            (Self::List(a), Self::List(b)) => cmp_lists(a, b),
+        }
+    }
+}
+
+fn cmp_lists(a: &[Item], b: &[Item]) -> Ordering {
+    let mut a = a.iter();
+    let mut b = b.iter();
+    loop {
+        let ordering = match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (Some(a), None) => a.cmp_null(),
+            (None, Some(b)) => b.cmp_null().reverse(),
+            (Some(a), Some(b)) => a.cmp(b),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+/// Known qualifiers are ordered by their position in [`QUALIFIERS`].
+/// Unknown qualifiers are considered after the known ones and are ordered lexically.
+fn comparable_qualifier(qualifier: &str) -> String {
+    match QUALIFIERS.iter().position(|v|*v == qualifier) {
+        Some(v) => v.to_string(),
+        None => format!("{}-{qualifier}", QUALIFIERS.len()),
+    }
+}
+
+/// Removes trailing null items (e.g. `1.0.0` -> `1`, `1-ga` -> `1`).
+/// Like in Maven, non-null sub-lists are skipped instead of ending the search.
+fn normalize(list: &mut Vec<Item>) {
+    let mut i = list.len();
+    while i > 0 {
+        i -= 1;
+        if list[i].is_null() {
+            list.remove(i);
+        } else if !matches!(list[i], Item::List(_)) {
+            break;
+        }
+    }
+}
+
+/// A Maven version, which orders like Maven's `ComparableVersion` (e.g. `1.0-SNAPSHOT` < `1.0` < `1.0.1` < `1.10`).
+#[derive(Debug, Clone)]
+pub struct ComparableVersion {
+    items: Vec<Item>,
+}
+
+impl ComparableVersion {
+    pub fn new(version: &str) -> Self {
+        let version = version.to_lowercase();
+        //Each '-' (and each switch between digits and letters) starts a new sub-list of the current list.
+        //Items are only ever added to the deepest list, so the lists are kept on a stack.
+        let mut stack: Vec<Vec<Item>> = vec![Vec::new()];
+        let mut is_digit = false;
+        let mut start = 0;
+
+        fn current(stack: &mut [Vec<Item>]) -> &mut Vec<Item> {
+            stack.last_mut().expect("the root list to always be on the stack")
+        }
+
+        for (i, c) in version.char_indices() {
+            match c {
+                '.' => {
+                    let item = if i == start { Item::int("") } else { Item::parse(is_digit, &version[start..i]) };
+                    current(&mut stack).push(item);
+                    start = i + 1;
+                }
+                '-' => {
+                    let item = if i == start { Item::int("") } else { Item::parse(is_digit, &version[start..i]) };
+                    current(&mut stack).push(item);
+                    start = i + 1;
+                    stack.push(Vec::new());
+                }
+                c if c.is_ascii_digit() => {
+                    if !is_digit && i > start {
+                        current(&mut stack).push(Item::string(&version[start..i], true));
+                        start = i;
+                        stack.push(Vec::new());
+                    }
+                    is_digit = true;
+                }
+                _ => {
+                    if is_digit && i > start {
+                        current(&mut stack).push(Item::parse(true, &version[start..i]));
+                        start = i;
+                        stack.push(Vec::new());
+                    }
+                    is_digit = false;
+                }
+            }
+        }
+        if version.len() > start {
+            current(&mut stack).push(Item::parse(is_digit, &version[start..]));
+        }
+
+        //Every sub-list is the last item of its parent, so fold them back, normalizing the deepest first.
+        let mut child = None;
+        while let Some(mut list) = stack.pop() {
+            if let Some(child) = child.take() {
+                list.push(Item::List(child));
+            }
+            normalize(&mut list);
+            child = Some(list);
+        }
+        let items = child.unwrap_or_default();
+        Self { items }
+    }
+}
+
+impl Ord for ComparableVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        cmp_lists(&self.items, &other.items)
+    }
+}
+impl PartialOrd for ComparableVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl PartialEq for ComparableVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for ComparableVersion {}
+
+#[cfg(test)]
+mod tests {
+    use super::ComparableVersion;
+
+    fn assert_order(versions: &[&str]) {
+        for pair in versions.windows(2) {
+            assert!(ComparableVersion::new(pair[0]) < ComparableVersion::new(pair[1]), "expected {} < {}", pair[0], pair[1]);
+        }
+    }
+    fn assert_equal(versions: &[&str]) {
+        for pair in versions.windows(2) {
+            assert!(ComparableVersion::new(pair[0]) == ComparableVersion::new(pair[1]), "expected {} == {}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn trailing_zeros_and_release_qualifiers_are_equal() {
+        assert_equal(&["1", "1.0", "1.0.0", "1-ga", "1.ga", "1-0", "1-final", "1-release", "1.0.0-ga"]);
+    }
+
+    #[test]
+    fn numbers_compare_numerically() {
+        assert_order(&["1.2", "1.9", "1.10", "1.10.1", "2", "10"]);
+        assert_order(&["1-foo2", "1-foo10"]);
+        assert_equal(&["1.010", "1.10"]);
+    }
+
+    #[test]
+    fn qualifiers_are_ordered() {
+        assert_order(&["1-alpha", "1-beta", "1-milestone", "1-rc", "1-snapshot", "1", "1-sp"]);
+        assert_order(&["1-sp", "1-foo", "1-1"]);
+        assert_equal(&["1-cr", "1-rc"]);
+    }
+
+    #[test]
+    fn short_qualifiers_followed_by_digits() {
+        assert_equal(&["1-a1", "1-alpha-1"]);
+        assert_equal(&["1-b2", "1-beta-2"]);
+        assert_equal(&["1-m3", "1-milestone-3"]);
+    }
+
+    #[test]
+    fn snapshots_come_before_their_release() {
+        assert_order(&["1.0-SNAPSHOT", "1.0", "1.0.1-SNAPSHOT", "1.0.1"]);
+        assert_order(&["1.0-alpha-1", "1.0-alpha-2", "1.0-beta-1", "1.0-rc1-SNAPSHOT", "1.0-rc1", "1.0-SNAPSHOT", "1.0"]);
+    }
+
+    #[test]
+    fn case_is_ignored() {
+        assert_equal(&["1.0-RC1", "1.0-rc1", "1.0-Rc-1"]);
+    }
+
+    #[test]
+    fn maven_specification_examples() {
+        assert_order(&["1", "1.1"]);
+        assert_order(&["1-snapshot", "1", "1-sp"]);
+        assert_order(&["1-foo", "1-1", "1.1"]);
+        assert_order(&["1-ga", "1-sp"]);
+        assert_order(&["1-ga.1", "1-sp.1"]);
+        assert_order(&["1-sp-1", "1-ga-1"]);
+    }
+}
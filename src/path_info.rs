@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::path::{Component, Path, PathBuf};
 use std::time::SystemTime;
 use chrono::{Datelike, Timelike};
@@ -7,12 +8,86 @@ use crate::err::GetRepoFileError;
 use crate::maven_metadata::{MavenMetadata, Snapshot, SnapshotVersion, Versioning};
 use crate::status::{Content, Return};
 
-type MavenMetadataReturn = (PathBuf, File, MavenMetadata, String);
+pub type MavenMetadataReturn = (PathBuf, File, MavenMetadata, String);
+
+/// Extensions of the checksum files, which are generated alongside every deployed file.
+pub const CHECKSUM_EXTENSIONS: [&str; 4] = ["md5", "sha1", "sha256", "sha512"];
+
+/// Extension of detached signatures, which are deployed alongside artifacts.
+pub const SIGNATURE_EXTENSION: &str = "asc";
+
+/// Returns true, if the file is a checksum or signature of another file.
+pub fn is_sibling_file(path: &Path) -> bool {
+    match path.extension().and_then(|v|v.to_str()) {
+        Some(v) => v == SIGNATURE_EXTENSION || CHECKSUM_EXTENSIONS.contains(&v),
+        None => false,
+    }
+}
+
+/// Splits a file-name into the name of the file, which its checksums and signature belong to, and their extensions.
+/// e.g. `foo.jar.asc.sha1` -> (`foo.jar`, `.asc.sha1`)
+pub fn split_sibling_extensions(file_name: &str) -> (&str, &str) {
+    let mut artifact_name = file_name;
+    while is_sibling_file(Path::new(artifact_name)) {
+        artifact_name = artifact_name.rsplit_once(".").map(|(name, _)|name).unwrap_or(artifact_name);
+    }
+    file_name.split_at(artifact_name.len())
+}
 
 pub struct SnapshotInfo<'a> {
     pub timestamp: &'a str,
     pub build_number: u64,
 }
+/// A file in a `-SNAPSHOT` version directory, which has no timestamp and build-number in its name (e.g. `foo-1.0-SNAPSHOT-sources.jar`).
+/// Some tools deploy snapshots like this, instead of using unique file-names.
+pub struct NonUniqueSnapshot<'a> {
+    pub artifact: &'a str,
+    /// The version without the `-SNAPSHOT` suffix
+    #[cfg_attr(not(feature = "put"), allow(dead_code))]
+    pub version: &'a str,
+    pub classifier: Option<&'a str>,
+    pub extension: Option<&'a str>,
+}
+impl<'a> NonUniqueSnapshot<'a> {
+    /// Returns `None`, if the path doesn't point to a non-unique snapshot file.
+    pub fn parse(path: &'a Path) -> Option<Self> {
+        let mut components = path.iter().rev().map(|v|v.to_str());
+        let file_name = components.next()??;
+        let version_dir = components.next()??;
+        let artifact = components.next()??;
+        Self::parse_file_name(artifact, version_dir, file_name)
+    }
+    /// Returns `None`, if `file_name` isn't a non-unique snapshot file of the artifact and version directory.
+    pub fn parse_file_name(artifact: &'a str, version_dir: &'a str, file_name: &'a str) -> Option<Self> {
+        let version = version_dir.strip_suffix("-SNAPSHOT")?;
+        let file_name = file_name.strip_prefix(artifact)?.strip_prefix("-")?.strip_prefix(version_dir)?;
+        let (file_name, extension) = file_name.rsplit_once(".").map(|(name, ext)|(name, Some(ext))).unwrap_or((file_name, None));
+        let classifier = if file_name.is_empty() {
+            None
+        } else {
+            Some(file_name.strip_prefix("-")?)
+        };
+        Some(Self{
+            artifact,
+            version,
+            classifier,
+            extension,
+        })
+    }
+    /// Returns the file-name of this file in the build `value` (e.g. `1.0-20240101.120000-1`).
+    pub fn unique_file_name(&self, value: &str) -> String {
+        let mut file_name = format!("{}-{value}", self.artifact);
+        if let Some(classifier) = self.classifier {
+            file_name.push('-');
+            file_name.push_str(classifier);
+        }
+        if let Some(extension) = self.extension {
+            file_name.push('.');
+            file_name.push_str(extension);
+        }
+        file_name
+    }
+}
 pub struct PathInfo<'a> {
     pub group: Vec<&'a str>,
     pub artifact: &'a str,
@@ -60,7 +135,7 @@ impl<'a> PathInfo<'a> {
                 group.push(i);
             }
         }
-        let file_name = match version {
+        let file_name = match file_name {
             None => return Err(Return{
                 status: actix_web::http::StatusCode::BAD_REQUEST,
                 content: Content::Str("Didn't find a File-Name in the path"),
@@ -111,7 +186,8 @@ impl<'a> PathInfo<'a> {
             Some(v) => (v, true),
             None => (version, false),
         };
-        let file_name = match file_name.strip_prefix(version).and_then(|v|v.strip_prefix("-")) {
+        //The classifier is optional, so there might not be anything after the version
+        let file_name = match file_name.strip_prefix(version).and_then(|v|if v.is_empty() { Some(v) } else { v.strip_prefix("-") }) {
             None => return Err(Return{
                 status: actix_web::http::StatusCode::BAD_REQUEST,
                 content: Content::Str("File didn't contain version"),
@@ -131,6 +207,7 @@ impl<'a> PathInfo<'a> {
                 Some(v) => v,
             };
             let (build_number, file_name) = match file_name.split_once("-") {
+                None if !file_name.is_empty() => (file_name, ""),
                 None => return Err(Return{
                     status: actix_web::http::StatusCode::BAD_REQUEST,
                     content: Content::Str("File didn't contain Snapshot build-number"),
@@ -170,7 +247,8 @@ impl<'a> PathInfo<'a> {
     pub async fn get_metadata(&self, repo: &str) -> Result<MavenMetadataReturn, Return> {
         self.get_metadata_int(repo, false, false).await
     }
-    async fn get_metadata_int(&self, repo: &str, snapshot: bool, lock_exclusive: bool) -> Result<MavenMetadataReturn, Return> {
+    /// Returns the path of the artifact-level maven-metadata, or the version-level one of snapshots.
+    fn metadata_path(&self, repo: &str, snapshot: bool) -> PathBuf {
         let mut metadata_path = PathBuf::new();
         metadata_path.push(repo);
         metadata_path.extend(&self.group);
@@ -179,59 +257,73 @@ impl<'a> PathInfo<'a> {
             metadata_path.push(format!("{}-SNAPSHOT", self.version));
         }
         metadata_path.push("maven-metadata.xml");
-        let file = match tokio::fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(&metadata_path)
-            .await
-        {
-            Err(err) => {
-                tracing::error!("Error creating or opening maven-metadata {}: {err}", metadata_path.display());
-                return Err(Return{
-                    status: actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
-                    content: Content::Str("Error creating or opening maven-metadata file"),
-                    content_type: actix_web::http::header::ContentType::plaintext(),
-                    header_map: None,
-                })
-            }
-            Ok(v) => v,
-        };
-        let mut file = if lock_exclusive {
-            let file = file.into_std().await;
-            //This potentially waits for any other tasks to 
-            let file = match tokio::task::spawn_blocking(||{
-                #[cfg(feature = "locking")]
-                let lock = file.lock();
-                #[cfg(not(feature = "locking"))]
-                let lock = Ok::<_, std::io::Error>(());
-
-                (file, lock)
-            }).await {
-                Ok((file, Ok(()))) => {
-                    file
-                }
-                Ok((_, Err(err))) => {
-                    tracing::error!("Error locking maven-metadata to String {}: {err}", metadata_path.display());
-                    return Err(Return{
-                        status: actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
-                        content: Content::Str("Error reading maven-metadata to String"),
-                        content_type: actix_web::http::header::ContentType::plaintext(),
-                        header_map: None,
-                    })
-                }
+        metadata_path
+    }
+    async fn get_metadata_int(&self, repo: &str, snapshot: bool, lock_exclusive: bool) -> Result<MavenMetadataReturn, Return> {
+        let metadata_path = self.metadata_path(repo, snapshot);
+        let mut file = loop {
+            let file = match tokio::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                //The existing metadata is read and only replaced via a temporary file
+                .truncate(false)
+                .open(&metadata_path)
+                .await
+            {
                 Err(err) => {
-                    tracing::error!("Error locking maven-metadata to String {}: {err}", metadata_path.display());
+                    tracing::error!("Error creating or opening maven-metadata {}: {err}", metadata_path.display());
                     return Err(Return{
                         status: actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
-                        content: Content::Str("Error reading maven-metadata to String"),
+                        content: Content::Str("Error creating or opening maven-metadata file"),
                         content_type: actix_web::http::header::ContentType::plaintext(),
                         header_map: None,
                     })
                 }
+                Ok(v) => v,
             };
-            File::from_std(file)
-        } else {file};
+            let file = if lock_exclusive {
+                let file = file.into_std().await;
+                //This potentially waits for any other tasks to 
+                let file = match tokio::task::spawn_blocking(||{
+                    #[cfg(feature = "locking")]
+                    let lock = file.lock();
+                    #[cfg(not(feature = "locking"))]
+                    let lock = Ok::<_, std::io::Error>(());
+
+                    (file, lock)
+                }).await {
+                    Ok((file, Ok(()))) => {
+                        file
+                    }
+                    Ok((_, Err(err))) => {
+                        tracing::error!("Error locking maven-metadata to String {}: {err}", metadata_path.display());
+                        return Err(Return{
+                            status: actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+                            content: Content::Str("Error reading maven-metadata to String"),
+                            content_type: actix_web::http::header::ContentType::plaintext(),
+                            header_map: None,
+                        })
+                    }
+                    Err(err) => {
+                        tracing::error!("Error locking maven-metadata to String {}: {err}", metadata_path.display());
+                        return Err(Return{
+                            status: actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+                            content: Content::Str("Error reading maven-metadata to String"),
+                            content_type: actix_web::http::header::ContentType::plaintext(),
+                            header_map: None,
+                        })
+                    }
+                };
+                File::from_std(file)
+            } else {file};
+            //Updates rename a new file over the locked one, which leaves waiting tasks with a lock on the replaced file.
+            //That lock doesn't protect anything, so the current file needs to be opened and locked again.
+            if lock_exclusive && !is_current_file(&file, &metadata_path).await {
+                continue;
+            }
+            break file;
+        };
         let mut contents = String::new();
         match file.read_to_string(&mut contents).await {
             Err(err) => {
@@ -262,9 +354,10 @@ impl<'a> PathInfo<'a> {
           MavenMetadata{
               group_id: self.dotted_group(),
               artifact_id: self.artifact.to_string(),
+              version: None,
               versioning: Versioning {
-                  latest: self.version.to_string(),
-                  release: self.version.to_string(),
+                  latest: None,
+                  release: None,
                   versions: None,
                   snapshot: None,
                   snapshot_versions: None,
@@ -276,13 +369,36 @@ impl<'a> PathInfo<'a> {
         Ok((metadata_path, file, metadata, contents))
     }
     pub async fn get_merged_metadata(&self, repo: &str, action: actix_web::http::Method) -> Result<Vec<MavenMetadataReturn>, Return> {
-        let mut out = Vec::new();
         let (path, file, mut metadata, _) = self.get_metadata_int(repo, false, true).await?;
-        
-        match &self.snapshot {
-            Some(snapshot) => {
-                let (snapshot_path, snapshot_file, mut snapshot_metadata, _) = self.get_metadata_int(repo, true, true).await?;
-                match action {
+        let mut snapshot = match &self.snapshot {
+            Some(_) => Some(self.get_metadata_int(repo, true, true).await?),
+            None => None,
+        };
+        let (changed, snapshot_changed) = self.update_metadata(&mut metadata, snapshot.as_mut().map(|(_, _, v, _)|v), &action)
+            .map_err(metadata_update_failed)?;
+
+        let mut out = Vec::new();
+        if let Some((snapshot_path, snapshot_file, snapshot_metadata, _)) = snapshot
+            && snapshot_changed {
+            out.push((snapshot_path, snapshot_file, snapshot_metadata));
+        }
+        if changed {
+            out.push((path, file, metadata));
+        }
+        let mut new_out = Vec::with_capacity(out.len());
+        for (path, file, mut metadata) in out {
+            let ser = serialize_metadata(&path, &mut metadata).ok_or_else(serialize_failed)?;
+            new_out.push((path, file, metadata, ser));
+        }
+        Ok(new_out)
+    }
+
+    /// Applies deploying (PUT) or deleting (DELETE) this file to the artifact-level `metadata` and, for snapshots, the version-level `snapshot_metadata`.
+    /// Returns, whether the artifact-level and the version-level metadata need to be written.
+    fn update_metadata(&self, metadata: &mut MavenMetadata, snapshot_metadata: Option<&mut MavenMetadata>, action: &actix_web::http::Method) -> Result<(bool, bool), &'static str> {
+        match (&self.snapshot, snapshot_metadata) {
+            (Some(snapshot), Some(snapshot_metadata)) => {
+                match *action {
                     actix_web::http::Method::DELETE => {
                         let value = format!("{}-{}-{}", self.version, snapshot.timestamp, snapshot.build_number);
                         snapshot_metadata.versioning.snapshot_versions.get_or_insert_default().snapshot_version.retain(|v|
@@ -307,33 +423,18 @@ impl<'a> PathInfo<'a> {
                                         let mut iter = version.value.rsplitn(2, "-");
                                         let build_number = match iter.next() {
                                             Some(v) => v,
-                                            None => return Err(Return{
-                                                status: actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
-                                                content: Content::Str("Next version doesn't have a build_number"),
-                                                content_type: actix_web::http::header::ContentType::plaintext(),
-                                                header_map: None,
-                                            })
+                                            None => return Err("Next version doesn't have a build_number"),
                                         };
-                                        let build_number = match u64::from_str_radix(build_number, 10) {
+                                        let build_number = match build_number.parse::<u64>() {
                                             Ok(v) => v,
                                             Err(err) => {
-                                                tracing::error!("Failed to parse build_number {}: build_number:{build_number} err:{err}", path.display());
-                                                return Err(Return{
-                                                    status: actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
-                                                    content: Content::Str("Next version doesn't have a valid build_number"),
-                                                    content_type: actix_web::http::header::ContentType::plaintext(),
-                                                    header_map: None,
-                                                })
+                                                tracing::error!("Failed to parse build_number of {}-SNAPSHOT: build_number:{build_number} err:{err}", self.version);
+                                                return Err("Next version doesn't have a valid build_number");
                                             }
                                         };
                                         let timestamp = match iter.next() {
-                                            Some(v) => v,
-                                            None => return Err(Return{
-                                                status: actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
-                                                content: Content::Str("Next version doesn't have a timestamp"),
-                                                content_type: actix_web::http::header::ContentType::plaintext(),
-                                                header_map: None,
-                                            })
+                                            Some(v) => v.strip_prefix(self.version).and_then(|v|v.strip_prefix("-")).unwrap_or(v),
+                                            None => return Err("Next version doesn't have a timestamp"),
                                         };
                                         snapshot_metadata.versioning.snapshot = Some(Snapshot{
                                             timestamp: timestamp.to_owned(),
@@ -346,68 +447,216 @@ impl<'a> PathInfo<'a> {
                                 }
                             }
                         }
-                        // No need to modify metadata here.
-                        // I currently see no issue with having a snapshot listed inside the main maven-metadata,
-                        // but having no snapshotVersion's in the Version-Level Maven-Metadata.
-                        out.push((snapshot_path, snapshot_file, snapshot_metadata));
+                        //Once the last build is gone, the snapshot version doesn't exist anymore,
+                        //so clients must not resolve it from the artifact's maven-metadata.
+                        let has_builds = snapshot_metadata.versioning.snapshot_versions.as_ref().is_some_and(|v|!v.snapshot_version.is_empty());
+                        let removed = !has_builds && metadata.versioning.versions.get_or_insert_default().version.remove(&format!("{}-SNAPSHOT", self.version));
+                        if removed {
+                            metadata.versioning.update_latest_release();
+                        }
+                        Ok((removed, true))
                     }
                     actix_web::http::Method::PUT => {
                         snapshot_metadata.versioning.snapshot = Some(Snapshot {
                             timestamp: snapshot.timestamp.to_owned(),
                             build_number: snapshot.build_number,
                         });
-                        snapshot_metadata.versioning.snapshot_versions.get_or_insert_default().snapshot_version.insert(SnapshotVersion {
-                            value: format!("{}-{}-{}", self.version, snapshot.timestamp, snapshot.build_number),
+                        let value = format!("{}-{}-{}", self.version, snapshot.timestamp, snapshot.build_number);
+                        let snapshot_versions = &mut snapshot_metadata.versioning.snapshot_versions.get_or_insert_default().snapshot_version;
+                        //A redeploy replaces the existing entry, instead of adding one with a different updated timestamp.
+                        snapshot_versions.retain(|v|
+                            v.value != value ||
+                                v.extension != self.extension.map(ToOwned::to_owned) ||
+                                v.classifier != self.classifier.map(ToOwned::to_owned)
+                        );
+                        snapshot_versions.insert(SnapshotVersion {
+                            value,
                             extension: self.extension.map(ToOwned::to_owned),
                             classifier: self.classifier.map(ToOwned::to_owned),
                             updated: get_timestamp_last_updated(),
                         });
-                        out.push((snapshot_path, snapshot_file, snapshot_metadata));
-                        if metadata.versioning.versions.get_or_insert_default().version.insert(format!("{}-SNAPSHOT", self.version)) {
-                            out.push((path, file, metadata));
-                        }
+                        let inserted = metadata.versioning.versions.get_or_insert_default().version.insert(format!("{}-SNAPSHOT", self.version));
+                        Ok((metadata.versioning.update_latest_release() || inserted, true))
                     }
-                    _ => { },
+                    _ => Ok((false, false)),
                 }
             },
-            None => {
-                if match action {
+            (Some(_), None) => Ok((false, false)),
+            (None, _) => {
+                let changed = match *action {
                     actix_web::http::Method::DELETE => metadata.versioning.versions.get_or_insert_default().version.remove(self.version),
                     actix_web::http::Method::PUT => metadata.versioning.versions.get_or_insert_default().version.insert(self.version.to_owned()),
-                   _ => false,
-                } {
-                    out.push((path, file, metadata));
-                }
+                    _ => false,
+                };
+                Ok((metadata.versioning.update_latest_release() || changed, false))
             }
-        };
+        }
+    }
+}
 
-        for (_, _, metadata) in &mut out {
-            metadata.versioning.last_updated = Some(get_timestamp_last_updated());
+/// Locks the maven-metadata files of all `infos` once each, like [`PathInfo::get_merged_metadata`] does for a single file.
+/// The files are locked in the order of their paths, with the artifact-level file before the version-level ones, so that concurrent updates don't deadlock.
+/// The locks are held, until the returned [`BatchMetadata`] is dropped.
+#[cfg_attr(not(feature = "put"), allow(dead_code))]
+pub async fn lock_batch_metadata(repo: &str, infos: &[PathInfo<'_>]) -> Result<BatchMetadata, Return> {
+    let mut artifacts = BTreeMap::<PathBuf, Vec<&PathInfo>>::new();
+    for info in infos {
+        artifacts.entry(info.metadata_path(repo, false)).or_default().push(info);
+    }
+    let mut out = BatchMetadata::default();
+    for (path, infos) in artifacts {
+        let (_, file, metadata, previous) = infos[0].get_metadata_int(repo, false, true).await?;
+        out.artifacts.insert(path, LockedMetadata::new(file, metadata, previous));
+        let versions = infos.iter()
+            .filter(|v|v.snapshot.is_some())
+            .map(|v|(v.metadata_path(repo, true), *v))
+            .collect::<BTreeMap<_, _>>();
+        for (path, info) in versions {
+            let (_, file, metadata, previous) = info.get_metadata_int(repo, true, true).await?;
+            out.versions.insert(path, LockedMetadata::new(file, metadata, previous));
         }
-        let out = {
-            let mut new_out = Vec::new();
-            for (path, file, meta)  in out {
-                let ser = match quick_xml::se::to_string(&meta) {
-                    Ok(v) => v,
-                    Err(err) => {
-                        tracing::error!("Failed to serialize maven metadata '{}' value '{meta:#?}': {err}", path.display());
-                        return Err(Return{
-                            status: actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
-                            content: Content::Str("Failed to serialize altered maven metadata"),
-                            content_type: actix_web::http::header::ContentType::plaintext(),
-                            header_map: None,
-                        })
-                    }
-                };
-                new_out.push((path, file, meta, ser));
-            }
-            new_out
-        };
+    }
+    Ok(out)
+}
 
+/// The maven-metadata files locked by [`lock_batch_metadata`].
+#[derive(Default)]
+#[cfg_attr(not(feature = "put"), allow(dead_code))]
+pub struct BatchMetadata {
+    /// The artifact-level files by their paths
+    artifacts: BTreeMap<PathBuf, LockedMetadata>,
+    /// The version-level files of snapshots by their paths
+    versions: BTreeMap<PathBuf, LockedMetadata>,
+}
+#[cfg_attr(not(feature = "put"), allow(dead_code))]
+struct LockedMetadata {
+    /// Holds the exclusive lock, until it is dropped
+    _lock: File,
+    metadata: MavenMetadata,
+    /// Empty, if the file didn't exist before
+    previous: String,
+    changed: bool,
+}
+#[cfg_attr(not(feature = "put"), allow(dead_code))]
+impl LockedMetadata {
+    fn new(lock: File, metadata: MavenMetadata, previous: String) -> Self {
+        Self {
+            _lock: lock,
+            metadata,
+            previous,
+            changed: false,
+        }
+    }
+}
+#[cfg_attr(not(feature = "put"), allow(dead_code))]
+impl BatchMetadata {
+    /// Returns the versioning of the locked version-level maven-metadata in the directory of `path` (relative to the repository).
+    /// Returns `None`, if it isn't locked.
+    pub fn snapshot_versioning(&self, repo: &str, path: &Path) -> Option<&Versioning> {
+        self.versions.get(&Path::new(repo).join(path.with_file_name("maven-metadata.xml")))
+            .map(|v|&v.metadata.versioning)
+    }
+    /// Returns the paths of the files, which didn't exist before they were locked.
+    pub fn created_files(&self) -> impl Iterator<Item = &Path> {
+        self.artifacts.iter()
+            .chain(&self.versions)
+            .filter(|(_, v)|v.previous.is_empty())
+            .map(|(path, _)|path.as_path())
+    }
+    /// Applies deploying all `infos`, which need to belong to the locked files.
+    /// Only the changed files are returned, with their new and previous contents.
+    pub fn update(&mut self, repo: &str, infos: &[PathInfo<'_>]) -> Result<Vec<MetadataChange>, &'static str> {
+        for info in infos {
+            let artifact = self.artifacts.get_mut(&info.metadata_path(repo, false)).ok_or("The maven-metadata of the artifact isn't locked")?;
+            let mut version = match info.snapshot {
+                Some(_) => Some(self.versions.get_mut(&info.metadata_path(repo, true)).ok_or("The maven-metadata of the snapshot version isn't locked")?),
+                None => None,
+            };
+            let (artifact_changed, version_changed) = info.update_metadata(&mut artifact.metadata, version.as_mut().map(|v|&mut v.metadata), &actix_web::http::Method::PUT)?;
+            artifact.changed |= artifact_changed;
+            if let Some(version) = version {
+                version.changed |= version_changed;
+            }
+        }
+        let mut out = Vec::new();
+        for (path, locked) in self.artifacts.iter_mut().chain(&mut self.versions).filter(|(_, v)|v.changed) {
+            let contents = serialize_metadata(path, &mut locked.metadata).ok_or("Failed to serialize altered maven metadata")?;
+            out.push(MetadataChange {
+                path: path.clone(),
+                contents,
+                previous: locked.previous.clone(),
+            });
+        }
         Ok(out)
     }
 }
 
+/// A locked maven-metadata file, which needs to be replaced with `contents`.
+#[cfg_attr(not(feature = "put"), allow(dead_code))]
+pub struct MetadataChange {
+    pub path: PathBuf,
+    pub contents: String,
+    /// Empty, if the file didn't exist before
+    pub previous: String,
+}
+
+/// Updates `lastUpdated` and serializes the metadata.
+#[cfg_attr(not(feature = "put"), allow(dead_code))]
+fn serialize_metadata(path: &Path, metadata: &mut MavenMetadata) -> Option<String> {
+    metadata.versioning.last_updated = Some(get_timestamp_last_updated());
+    match quick_xml::se::to_string(&metadata) {
+        Ok(v) => Some(v),
+        Err(err) => {
+            tracing::error!("Failed to serialize maven metadata '{}' value '{metadata:#?}': {err}", path.display());
+            None
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "put"), allow(dead_code))]
+fn serialize_failed() -> Return {
+    Return{
+        status: actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+        content: Content::Str("Failed to serialize altered maven metadata"),
+        content_type: actix_web::http::header::ContentType::plaintext(),
+        header_map: None,
+    }
+}
+
+#[cfg_attr(not(feature = "put"), allow(dead_code))]
+pub fn metadata_update_failed(message: &'static str) -> Return {
+    Return{
+        status: actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+        content: Content::Str(message),
+        content_type: actix_web::http::header::ContentType::plaintext(),
+        header_map: None,
+    }
+}
+
+/// Returns the current time in the format used in the file-names of snapshot builds.
+#[cfg_attr(not(feature = "put"), allow(dead_code))]
+pub fn get_timestamp_snapshot() -> String{
+    let time = chrono::DateTime::<chrono::Utc>::from(SystemTime::now());
+    format!("{:04}{:02}{:02}.{:02}{:02}{:02}", time.year(), time.month(), time.day(), time.hour(), time.minute(), time.second())
+}
+
+/// Returns true, if `file` is still the file at `path`, and not one that was replaced since it was opened.
+#[cfg(unix)]
+async fn is_current_file(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (file.metadata().await, tokio::fs::metadata(path).await) {
+        (Ok(file), Ok(path)) => file.dev() == path.dev() && file.ino() == path.ino(),
+        (_, Err(err)) if err.kind() == std::io::ErrorKind::NotFound => false,
+        //Retrying wouldn't help with other errors, which reading the file will report.
+        _ => true,
+    }
+}
+/// Without inode numbers, files are assumed to not have been replaced.
+#[cfg(not(unix))]
+async fn is_current_file(_: &File, _: &Path) -> bool {
+    true
+}
+
 pub fn get_timestamp_last_updated() -> String{
     let time = chrono::DateTime::<chrono::Utc>::from(SystemTime::now());
     format!("{:04}{:02}{:02}{:02}{:02}{:02}", time.year(), time.month(), time.day(), time.hour(), time.minute(), time.second())
@@ -0,0 +1,120 @@
+#![cfg(feature = "put")]
+use crate::path_info::PathInfo;
+
+/// The parts of a POM, which identify the artifact it belongs to.
+#[derive(Debug, serde_derive::Deserialize)]
+#[serde(rename_all="camelCase")]
+struct Pom {
+    #[serde(default)]
+    group_id: Option<String>,
+    #[serde(default)]
+    artifact_id: Option<String>,
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    parent: Option<Parent>,
+}
+#[derive(Debug, serde_derive::Deserialize)]
+#[serde(rename_all="camelCase")]
+struct Parent {
+    #[serde(default)]
+    group_id: Option<String>,
+    #[serde(default)]
+    version: Option<String>,
+}
+
+/// Checks, that the POM is well-formed and that its groupId, artifactId and version match the path it is deployed to.
+/// The groupId and version may be inherited from the parent.
+/// Values containing a property reference (e.g. `${revision}`) can't be resolved here, so they aren't checked.
+/// Returns the reason, why the POM is rejected.
+pub fn validate(contents: &[u8], info: &PathInfo<'_>) -> Result<(), String> {
+    let contents = match std::str::from_utf8(contents) {
+        Ok(v) => v,
+        Err(err) => return Err(format!("The POM is not valid UTF-8: {err}")),
+    };
+    let pom: Pom = match quick_xml::de::from_str(contents) {
+        Ok(v) => v,
+        Err(err) => return Err(format!("Failed to parse the POM: {err}")),
+    };
+    let parent = pom.parent.as_ref();
+
+    let group_id = pom.group_id.as_deref().or(parent.and_then(|v|v.group_id.as_deref()));
+    let version = pom.version.as_deref().or(parent.and_then(|v|v.version.as_deref()));
+    let expected_version = match info.snapshot {
+        Some(_) => format!("{}-SNAPSHOT", info.version),
+        None => info.version.to_owned(),
+    };
+
+    for (name, value, expected) in [
+        ("groupId", group_id, info.dotted_group()),
+        ("artifactId", pom.artifact_id.as_deref(), info.artifact.to_owned()),
+        ("version", version, expected_version),
+    ] {
+        let value = match value {
+            Some(v) => v.trim(),
+            None => return Err(format!("The POM doesn't specify a {name}")),
+        };
+        if value.contains("${") {
+            continue;
+        }
+        if value != expected {
+            return Err(format!("The POM's {name} '{value}' doesn't match the {name} '{expected}' of the deploy path"));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::path_info::PathInfo;
+    use super::validate;
+
+    fn validate_at(path: &str, pom: &str) -> Result<(), String> {
+        let info = PathInfo::parse(Path::new(path)).expect("valid artifact path");
+        validate(pom.as_bytes(), &info)
+    }
+
+    #[test]
+    fn matching_coordinates_are_accepted() {
+        let pom = "<project><groupId>com.example</groupId><artifactId>foo</artifactId><version>1.0</version></project>";
+        assert_eq!(validate_at("com/example/foo/1.0/foo-1.0.pom", pom), Ok(()));
+    }
+
+    #[test]
+    fn group_and_version_are_inherited_from_the_parent() {
+        let pom = "<project><parent><groupId>com.example</groupId><artifactId>parent</artifactId><version>1.0</version></parent><artifactId>foo</artifactId></project>";
+        assert_eq!(validate_at("com/example/foo/1.0/foo-1.0.pom", pom), Ok(()));
+    }
+
+    #[test]
+    fn snapshot_builds_expect_the_snapshot_version() {
+        let pom = "<project><groupId>com.example</groupId><artifactId>foo</artifactId><version>1.0-SNAPSHOT</version></project>";
+        assert_eq!(validate_at("com/example/foo/1.0-SNAPSHOT/foo-1.0-20240101.120000-1.pom", pom), Ok(()));
+    }
+
+    #[test]
+    fn property_references_are_not_checked() {
+        let pom = "<project><groupId>com.example</groupId><artifactId>foo</artifactId><version>${revision}</version></project>";
+        assert_eq!(validate_at("com/example/foo/1.0/foo-1.0.pom", pom), Ok(()));
+    }
+
+    #[test]
+    fn mismatching_coordinates_are_rejected() {
+        let pom = "<project><groupId>com.other</groupId><artifactId>foo</artifactId><version>1.0</version></project>";
+        assert!(validate_at("com/example/foo/1.0/foo-1.0.pom", pom).unwrap_err().contains("groupId"));
+        let pom = "<project><groupId>com.example</groupId><artifactId>bar</artifactId><version>1.0</version></project>";
+        assert!(validate_at("com/example/foo/1.0/foo-1.0.pom", pom).unwrap_err().contains("artifactId"));
+        let pom = "<project><groupId>com.example</groupId><artifactId>foo</artifactId><version>2.0</version></project>";
+        assert!(validate_at("com/example/foo/1.0/foo-1.0.pom", pom).unwrap_err().contains("version"));
+    }
+
+    #[test]
+    fn missing_coordinates_and_malformed_poms_are_rejected() {
+        let pom = "<project><artifactId>foo</artifactId><version>1.0</version></project>";
+        assert!(validate_at("com/example/foo/1.0/foo-1.0.pom", pom).unwrap_err().contains("doesn't specify a groupId"));
+        assert!(validate_at("com/example/foo/1.0/foo-1.0.pom", "<project>").is_err());
+        let info = PathInfo::parse(Path::new("com/example/foo/1.0/foo-1.0.pom")).expect("valid artifact path");
+        assert!(validate(&[0xff, 0xfe], &info).is_err());
+    }
+}
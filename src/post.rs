@@ -0,0 +1,125 @@
+#![cfg(feature = "put")]
+use std::net::IpAddr;
+use std::path::PathBuf;
+use crate::audit::{Actor, FileSummary};
+use crate::auth::BasicAuthentication;
+use crate::err::GetRepoFileError;
+use crate::hosted_request::{parse_hosted_request, HostedRequest};
+use crate::repository::{get_repo_config, lock_staging_repo, Repository};
+use crate::status::{Content, Return};
+
+/// POST requests trigger actions on a hosted repository, which are selected by the query, e.g. `POST /releases/?bundle`.
+/// - `bundle`: deploys an archive, see [`crate::bundle::deploy_bundle`]
+/// - `stage`: opens a staging repository for the repository
+/// - `promote`/`drop`: moves the files of a staging repository into its target or discards them
+/// - `copy=<repo>`/`move=<repo>`: copies or moves the requested path into another hosted repository
+pub async fn post_repo_file(req: actix_web::HttpRequest, auth: Result<BasicAuthentication, Return>, client_ip: Option<IpAddr>, data: actix_web::web::Payload) -> Return {
+    let query = req.query_string();
+    let action = query.split("&").next().unwrap_or_default();
+    let (action, value) = action.split_once("=").unwrap_or((action, ""));
+    let HostedRequest { repo, config, path, str_path, auth, staging_guard } = match parse_hosted_request(&req, auth, client_ip, action, "It's forbidden to modify a repo, which has remotes.").await {
+        Ok(v) => v,
+        Err(err) => return err,
+    };
+
+    let mut actor = Actor::new(client_ip);
+    let ret = match action {
+        "bundle" => {
+            let authorized = config.check_auth(actix_web::http::Method::PUT, auth.clone(), &str_path);
+            actor.authenticated(auth.as_ref(), &authorized);
+            match authorized {
+                Err(err) => err,
+                Ok(_) => crate::bundle::deploy_bundle(repo.clone(), &config, &actor, path.clone(), data).await,
+            }
+        },
+        "stage" => {
+            let authorized = config.check_auth(actix_web::http::Method::PUT, auth.clone(), &str_path);
+            actor.authenticated(auth.as_ref(), &authorized);
+            match authorized {
+                Err(err) => err,
+                Ok(_) => crate::staging::open_staging_repo(&repo, &config).await,
+            }
+        },
+        "promote" => {
+            //Promoting writes to the target, so the target decides who may do it.
+            let authorized = match config.staging_target.as_deref().map(get_repo_config) {
+                Some(Ok(v)) => v.check_auth(actix_web::http::Method::PUT, auth.clone(), &str_path),
+                Some(Err(err)) => Err(err.to_return()),
+                None => config.check_auth(actix_web::http::Method::PUT, auth.clone(), &str_path),
+            };
+            actor.authenticated(auth.as_ref(), &authorized);
+            match authorized {
+                Err(err) => err,
+                Ok(_) => {
+                    //Promoting waits for the requests, which write to the staging repository, so this one must not count as one.
+                    drop(staging_guard);
+                    crate::staging::promote_staging_repo(&repo, &config, &actor).await
+                },
+            }
+        },
+        "drop" => {
+            let authorized = config.check_auth(actix_web::http::Method::DELETE, auth.clone(), &str_path);
+            actor.authenticated(auth.as_ref(), &authorized);
+            match authorized {
+                Err(err) => err,
+                Ok(_) => {
+                    drop(staging_guard);
+                    crate::staging::drop_staging_repo(&repo, &config).await
+                },
+            }
+        },
+        "copy" | "move" => copy_or_move(&repo, &config, auth, &mut actor, path.clone(), value, action == "move").await,
+        _ => Return {
+            status: actix_web::http::StatusCode::BAD_REQUEST,
+            content: Content::Str("Unknown action. Supported actions are: bundle, stage, promote, drop, copy, move"),
+            content_type: actix_web::http::header::ContentType::plaintext(),
+            header_map: None,
+        },
+    };
+    crate::audit::record(&repo, &actor, action, &path, &FileSummary::default(), ret.status).await;
+    ret
+}
+
+async fn copy_or_move(repo: &str, config: &Repository, auth: Option<BasicAuthentication>, actor: &mut Actor, path: PathBuf, target: &str, remove_source: bool) -> Return {
+    if target.is_empty() || target == repo {
+        return Return {
+            status: actix_web::http::StatusCode::BAD_REQUEST,
+            content: Content::Str("Copying or moving needs another repository as target, e.g. '?copy=thirdparty'"),
+            content_type: actix_web::http::header::ContentType::plaintext(),
+            header_map: None,
+        };
+    }
+    let target_config = match get_repo_config(target) {
+        Ok(v) => v,
+        Err(e) => return e.to_return(),
+    };
+    if !target_config.upstreams.is_empty() {
+        return Return {
+            status: actix_web::http::StatusCode::FORBIDDEN,
+            content: Content::Str("It's forbidden to modify a repo, which has remotes."),
+            content_type: actix_web::http::header::ContentType::plaintext(),
+            header_map: None,
+        }
+    }
+    let str_path = path.to_str().unwrap_or_default();
+    let source_method = if remove_source { actix_web::http::Method::DELETE } else { actix_web::http::Method::GET };
+    let authorized = config.check_auth(source_method, auth.clone(), str_path);
+    actor.authenticated(auth.as_ref(), &authorized);
+    if let Err(err) = authorized {
+        return err;
+    }
+    let authorized = target_config.check_auth(actix_web::http::Method::PUT, auth.clone(), str_path);
+    actor.authenticated(auth.as_ref(), &authorized);
+    if let Err(err) = authorized {
+        return err;
+    }
+    //Like any other request, which writes to a staging repository, this keeps it from being promoted or dropped meanwhile.
+    let _target_guard = match target_config.staging_target {
+        Some(_) => match lock_staging_repo(target).await {
+            Some(v) => Some(v),
+            None => return GetRepoFileError::NotFound.to_return(),
+        },
+        None => None,
+    };
+    crate::transfer::transfer(repo, config, actor, path, target, &target_config, remove_source).await
+}
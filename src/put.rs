@@ -1,171 +1,513 @@
 #![cfg(feature = "put")]
 use std::io::{Cursor, Error, ErrorKind};
-use std::path::{Component, Path, PathBuf};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 use digest::Digest;
 use futures::TryStreamExt;
 use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::task::JoinSet;
 use tokio_util::compat::FuturesAsyncReadCompatExt;
+use tokio_util::either::Either;
+use crate::audit::{Actor, FileSummary};
 use crate::auth::BasicAuthentication;
 use crate::err::GetRepoFileError;
-use crate::path_info::PathInfo;
-use crate::repository::get_repo_config;
+use crate::hosted_request::{parse_hosted_request, HostedRequest};
+use crate::maven_metadata::{MavenMetadata, Versioning};
+use crate::path_info::{get_timestamp_snapshot, lock_batch_metadata, metadata_update_failed, split_sibling_extensions, MavenMetadataReturn, NonUniqueSnapshot, PathInfo, CHECKSUM_EXTENSIONS, SIGNATURE_EXTENSION};
+use crate::repository::{RedeployPolicy, Repository};
+use crate::checksum_deploy::StoredDigests;
+use crate::request_digest::RequestDigests;
 use crate::status::{Content, Return};
+use crate::temp_file;
+use crate::webhook::{ChangedFile, EventKind};
 
-pub async fn put_repo_file(req: actix_web::HttpRequest, auth: Result<BasicAuthentication, Return>, data: actix_web::web::Payload) -> Return {
-    let uri = req.full_url();
-    let path = uri.path();
-    let path = path.strip_prefix("/").unwrap_or(path);
-    let path = PathBuf::from(path);
-    let repo;
-    let path = {
-        let mut iter = path.components();
-        loop {
-            match iter.next() {
-                Some(Component::Normal(v)) => {
-                    match v.to_str() {
-                        Some(v) => {
-                            repo = Arc::from(v);
-                            break;
-                        },
-                        None => return Return{
-                            status: actix_web::http::StatusCode::BAD_REQUEST,
-                            content: Content::Str("A part of the request was not valid UTF-8"),
-                            content_type: actix_web::http::header::ContentType::plaintext(),
-                            header_map: None,
-                        },
-                    }
+pub async fn put_repo_file(req: actix_web::HttpRequest, auth: Result<BasicAuthentication, Return>, client_ip: Option<IpAddr>, data: actix_web::web::Payload) -> Return {
+    let HostedRequest { repo, config, path, str_path, auth, staging_guard: _staging_guard } = match parse_hosted_request(&req, auth, client_ip, "put", "It's forbidden to deploy to a repo, which has remotes.").await {
+        Ok(v) => v,
+        Err(err) => return err,
+    };
+
+    let mut actor = Actor::new(client_ip);
+    let authorized = config.check_auth(actix_web::http::Method::PUT, auth.clone(), &str_path);
+    actor.authenticated(auth.as_ref(), &authorized);
+    let result = match authorized {
+        Ok(_) => put_authorized(&req, &repo, &config, path.clone(), data).await,
+        Err(err) => Err(err),
+    };
+    let (stored_path, ret) = match result {
+        Ok(v) => (v, created()),
+        Err(err) => (path, err),
+    };
+    let summary = if ret.status.is_success() {
+        FileSummary::of(&Path::new(&*repo).join(&stored_path)).await
+    } else {
+        FileSummary::default()
+    };
+    crate::audit::record(&repo, &actor, "put", &stored_path, &summary, ret.status).await;
+    //Uploaded checksums and maven-metadata files don't change anything
+    if ret.status.is_success() && stored_path.file_name().and_then(|v|v.to_str()).is_some_and(crate::bundle::is_deployable) {
+        crate::events::publish(&repo, crate::events::ChangeKind::Deployed, &stored_path);
+        crate::webhook::notify(&repo, &config, EventKind::Deploy, &[ChangedFile {
+            path: stored_path,
+            summary,
+        }]);
+    }
+    ret
+}
+
+async fn put_authorized(req: &actix_web::HttpRequest, repo: &Arc<str>, config: &Arc<Repository>, path: PathBuf, data: actix_web::web::Payload) -> Result<PathBuf, Return> {
+    let request_digests = match RequestDigests::from_headers(req.headers()) {
+        Ok(v) => v,
+        Err(message) => return Err(Return {
+            status: actix_web::http::StatusCode::BAD_REQUEST,
+            content: Content::String(message),
+            content_type: actix_web::http::header::ContentType::plaintext(),
+            header_map: None,
+        }),
+    };
+
+    let data = data.map_err(std::io::Error::other).into_async_read().compat();
+    //A checksum deploy copies an already stored file with the same content, instead of sending the body.
+    let is_checksum_file = path.extension().and_then(|v|v.to_str()).is_some_and(|v|CHECKSUM_EXTENSIONS.contains(&v));
+    let data = if !is_checksum_file && crate::checksum_deploy::is_checksum_deploy(req.headers()) {
+        Either::Left(crate::checksum_deploy::find_stored_file(repo, &request_digests).await?)
+    } else {
+        Either::Right(data)
+    };
+
+    deploy_file(repo, config, path, &request_digests, data).await
+}
+
+/// Stores `data` at `path` (relative to the repository) and updates the maven-metadata, like a single PUT does.
+/// Returns the path the file was stored under, which differs from `path` for non-unique snapshots.
+pub async fn deploy_file<D: tokio::io::AsyncRead + Unpin>(repo: &Arc<str>, config: &Arc<Repository>, path: PathBuf, request_digests: &RequestDigests, mut data: D) -> Result<PathBuf, Return> {
+    //The maven-metadata is maintained by this server, based on the deployed files.
+    if path.file_name().and_then(|v|v.to_str()).is_some_and(|v|v.starts_with("maven-metadata.xml")) {
+        return Ok(path);
+    }
+    let requested_path = path;
+    let path = unique_snapshot_path(repo, requested_path.clone()).await?;
+    let file_path = Path::new(&**repo).join(path.as_path());
+    let extension = file_path.extension().and_then(|v|v.to_str());
+    if let Some(extension) = extension
+        && CHECKSUM_EXTENSIONS.contains(&extension) {
+        let ret = put_checksum(&file_path, extension, data).await;
+        return if ret.status.is_success() { Ok(path) } else { Err(ret) };
+    }
+
+    //Signatures don't show up in the maven-metadata
+    let info = if extension == Some(SIGNATURE_EXTENSION) {
+        if let Some(ret) = compare_signature(&file_path, &mut data).await {
+            return if ret.status.is_success() { Ok(path) } else { Err(ret) };
+        }
+        None
+    } else {
+        Some(PathInfo::parse(path.as_path())?)
+    };
+    let max_file_size = config.max_file_size.unwrap_or(crate::DEFAULT_MAX_FILE_SIZE);
+    //POMs are small, so they get validated in memory before anything is written.
+    let data = match &info {
+        Some(info) if info.extension == Some("pom") => {
+            let mut pom = Vec::new();
+            match (&mut data).take(max_file_size + 1).read_to_end(&mut pom).await {
+                Ok(_) => {},
+                Err(err) => {
+                    tracing::error!("Failed to read uploaded POM {}: {err}", path.display());
+                    return Err(GetRepoFileError::FileWriteFailed.to_return());
                 }
-                Some(_) => continue,
-                None => return Return{
-                    status: actix_web::http::StatusCode::NOT_FOUND,
-                    content: Content::None,
+            }
+            if pom.len() as u64 > max_file_size {
+                return Err(GetRepoFileError::PutFileTooLarge.to_return());
+            }
+            if let Err(message) = crate::pom::validate(&pom, info) {
+                return Err(Return {
+                    status: actix_web::http::StatusCode::BAD_REQUEST,
+                    content: Content::String(message),
                     content_type: actix_web::http::header::ContentType::plaintext(),
                     header_map: None,
-                },
+                });
             }
-        }
-        PathBuf::from_iter(iter)
+            Either::Left(Cursor::new(pom))
+        },
+        _ => Either::Right(data),
     };
-    let auth = match auth {
-        Err(err) if err.status == actix_web::http::StatusCode::FORBIDDEN => None,
-        Err(err) => return err,
-        Ok(v) => Some(v),
+    create_file_dirs(repo, &path).await?;
+    let mut locked = match &info {
+        Some(info) => Some(lock_batch_metadata(repo, std::slice::from_ref(info)).await?),
+        None => None,
+    };
+    //Only assigned while holding the lock, so that concurrent deploys don't store different files under the same build.
+    let path = match (&locked, NonUniqueSnapshot::parse(&requested_path)) {
+        (Some(locked), Some(non_unique)) => {
+            let value = next_snapshot_value(&non_unique, locked.snapshot_versioning(repo, &requested_path));
+            requested_path.with_file_name(non_unique.unique_file_name(&value))
+        },
+        _ => path.clone(),
     };
-    if path.components().any(|v|
-        match v {
-            Component::ParentDir => true,
-            Component::RootDir => true,
-            Component::Prefix(_) => true,
-            _ => false,
+    let file_path = Path::new(&**repo).join(path.as_path());
+    let metadata = match &mut locked {
+        Some(locked) => locked.update(repo, &[PathInfo::parse(&path)?]).map_err(metadata_update_failed)?,
+        None => Vec::new(),
+    };
+
+    let is_snapshot = path.parent()
+        .and_then(|v|v.file_name())
+        .and_then(|v|v.to_str())
+        .is_some_and(|v|v.ends_with("-SNAPSHOT"));
+    let redeploy_policy = if is_snapshot {
+        RedeployPolicy::Overwrite
+    } else if info.is_none() {
+        //A signature uploaded concurrently is only kept, if it is the same.
+        RedeployPolicy::IdenticalOnly
+    } else {
+        config.redeploy_policy.unwrap_or_default()
+    };
+    //Fail early, instead of after receiving the whole file. The final check is done atomically in put_file.
+    match tokio::fs::try_exists(&file_path).await {
+        Ok(false) => {},
+        Ok(true) if redeploy_policy == RedeployPolicy::Immutable => return Err(file_exists()),
+        Ok(true) => {},
+        Err(err) => {
+            tracing::error!("Failed to check if {} exists: {err}", file_path.display());
+            return Err(GetRepoFileError::OpenFile.to_return());
         }
-    ) {
-        return GetRepoFileError::BadRequestPath.to_return();
     }
-    if path.has_root() {
-        return GetRepoFileError::BadRequestPath.to_return();
+    let temp_path = temp_file::temp_path(&file_path);
+    let file = match tokio::fs::OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .open(&temp_path)
+        .await
+    {
+        Ok(v) => v,
+        Err(err) => {
+            tracing::error!("Failed to create temporary file while deploying {}: {err}", path.display());
+            return Err(Return {
+                status: actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+                content: Content::Str("Failed creating file"),
+                content_type: actix_web::http::header::ContentType::plaintext(),
+                header_map: None,
+            })
+        }
+    };
+    put_file(file, file_path, temp_path, redeploy_policy, request_digests, max_file_size, data).await?;
+    for change in metadata {
+        replace_metadata_file(change.path, change.contents, max_file_size).await?;
     }
-    let str_path = match path.to_str() {
-        None => return GetRepoFileError::InvalidUTF8.to_return(),
+    drop(locked);
+
+    if info.is_some_and(|v|v.snapshot.is_some()) && config.snapshot_retention.is_some()
+        && let Some(version_dir) = path.parent() {
+        crate::snapshot_retention::schedule_prune(repo.clone(), config.clone(), version_dir.to_path_buf());
+    }
+
+    Ok(path)
+}
+
+fn file_exists() -> Return {
+    Return {
+        status: actix_web::http::StatusCode::CONFLICT,
+        content: Content::Str("File already exists"),
+        content_type: actix_web::http::header::ContentType::plaintext(),
+        header_map: None,
+    }
+}
+
+fn created() -> Return {
+    Return{
+        status: actix_web::http::StatusCode::CREATED,
+        content: Content::Str(""),
+        content_type: actix_web::http::header::ContentType::plaintext(),
+        header_map: None,
+    }
+}
+
+/// Tools deploying non-unique snapshots upload files like `foo-1.0-SNAPSHOT.jar`.
+/// Those get stored under a timestamped name instead, as if Maven had deployed them.
+/// Checksums and signatures get attached to the newest build, which contains the file they belong to.
+/// Other files only get a name in a new build here, which locates their maven-metadata.
+/// Their actual build depends on the other builds, so it is only assigned while holding the lock of the maven-metadata.
+pub async fn unique_snapshot_path(repo: &str, path: PathBuf) -> Result<PathBuf, Return> {
+    let file_name = match path.file_name().and_then(|v|v.to_str()) {
         Some(v) => v,
+        None => return Ok(path),
+    };
+    let (artifact_name, sibling_extensions) = split_sibling_extensions(file_name);
+    let artifact_path = path.with_file_name(artifact_name);
+    let info = match NonUniqueSnapshot::parse(&artifact_path) {
+        Some(v) => v,
+        None => return Ok(path),
     };
-    let str_path = str_path.strip_prefix("/").unwrap_or(str_path);
-    let str_path = str_path.strip_suffix("/").unwrap_or(str_path);
 
-    let config = match get_repo_config(&repo) {
-        Ok(v) => v,
-        Err(e) => return e.to_return(),
+    let value = if sibling_extensions.is_empty() {
+        new_snapshot_value(info.version, None)
+    } else {
+        let versioning = read_snapshot_versioning(repo, &artifact_path).await?;
+        match versioning.as_ref().and_then(|v|v.latest_snapshot_version(info.classifier, info.extension)) {
+            Some(v) => v.value.clone(),
+            None => return Err(Return {
+                status: actix_web::http::StatusCode::CONFLICT,
+                content: Content::Str("The file this checksum or signature belongs to doesn't exist"),
+                content_type: actix_web::http::header::ContentType::plaintext(),
+                header_map: None,
+            }),
+        }
     };
+    let mut unique_name = info.unique_file_name(&value);
+    unique_name.push_str(sibling_extensions);
+    Ok(path.with_file_name(unique_name))
+}
 
+/// Reads the versioning of the version-level maven-metadata in the directory of `path` (relative to the repository).
+/// Returns `None`, if there is no maven-metadata yet.
+async fn read_snapshot_versioning(repo: &str, path: &Path) -> Result<Option<Versioning>, Return> {
+    let metadata_path = Path::new(repo).join(path.with_file_name("maven-metadata.xml"));
+    match tokio::fs::read_to_string(&metadata_path).await {
+        //An empty file is left behind by an update, which failed before writing anything.
+        Ok(contents) if contents.is_empty() => Ok(None),
+        Ok(contents) => match quick_xml::de::from_str::<MavenMetadata>(&contents) {
+            Ok(v) => Ok(Some(v.versioning)),
+            Err(err) => {
+                tracing::error!("Failed to parse maven-metadata.xml {}: {err}", metadata_path.display());
+                Err(Return{
+                    status: actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+                    content: Content::Str("Error deserializing maven-metadata"),
+                    content_type: actix_web::http::header::ContentType::plaintext(),
+                    header_map: None,
+                })
+            }
+        },
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => {
+            tracing::error!("Error reading maven-metadata {}: {err}", metadata_path.display());
+            Err(GetRepoFileError::OpenFile.to_return())
+        }
+    }
+}
+
+/// Returns the build (e.g. `1.0-20240101.120000-1`) a non-unique snapshot file gets added to.
+/// That's the newest build, unless it already contains a file with the same classifier and extension.
+/// In that case a new build is started.
+fn next_snapshot_value(info: &NonUniqueSnapshot, versioning: Option<&Versioning>) -> String {
+    let snapshot_versions = versioning.and_then(|v|v.snapshot_versions.as_ref()).map(|v|&v.snapshot_version);
+    if let Some(snapshot) = versioning.and_then(|v|v.snapshot.as_ref()) {
+        let current = format!("{}-{}-{}", info.version, snapshot.timestamp, snapshot.build_number);
+        if !snapshot_versions.is_some_and(|v|v.iter().any(|v|
+            v.value == current &&
+                v.classifier.as_deref() == info.classifier &&
+                v.extension.as_deref() == info.extension
+        )) {
+            return current;
+        }
+    }
+    new_snapshot_value(info.version, versioning)
+}
+
+/// Returns a new build (e.g. `1.0-20240101.120000-2`) of the snapshot `version` (without `-SNAPSHOT`), following the builds in `versioning`.
+pub fn new_snapshot_value(version: &str, versioning: Option<&Versioning>) -> String {
+    let snapshot_versions = versioning.and_then(|v|v.snapshot_versions.as_ref()).map(|v|&v.snapshot_version);
+    let build_number = snapshot_versions.into_iter()
+        .flatten()
+        .filter_map(|v|v.build_number())
+        .chain(versioning.and_then(|v|v.snapshot.as_ref()).map(|v|v.build_number))
+        .max()
+        .unwrap_or(0) + 1;
+    format!("{version}-{}-{build_number}", get_timestamp_snapshot())
+}
 
-    if !config.upstreams.is_empty() {
-        return Return {
-            status: actix_web::http::StatusCode::FORBIDDEN,
-            content: Content::Str("It's forbidden to deploy to a repo, which has remotes."),
+/// Maximum accepted size of an uploaded checksum file.
+/// Some tools append the file-name after the hash, so this is a bit larger than the largest hash.
+const MAX_CHECKSUM_FILE_SIZE: u64 = 1024;
+
+/// Checksums are generated by the server, when the file they belong to gets deployed.
+/// Clients still upload their own checksums afterward, so those get compared to the generated ones, without writing anything.
+async fn put_checksum<D: tokio::io::AsyncRead + Unpin>(file_path: &Path, extension: &str, data: D) -> Return {
+    let artifact_path = file_path.with_extension("");
+    match tokio::fs::try_exists(&artifact_path).await {
+        Ok(true) => {},
+        Ok(false) => return Return {
+            status: actix_web::http::StatusCode::CONFLICT,
+            content: Content::Str("The file this checksum belongs to doesn't exist"),
             content_type: actix_web::http::header::ContentType::plaintext(),
             header_map: None,
+        },
+        Err(err) => {
+            tracing::error!("Failed to check if {} exists: {err}", artifact_path.display());
+            return GetRepoFileError::OpenFile.to_return();
         }
     }
-    
-    match config.check_auth(actix_web::http::Method::PUT, auth, str_path) {
-        Err(err) => return err,
+
+    let mut uploaded = String::new();
+    match data.take(MAX_CHECKSUM_FILE_SIZE + 1).read_to_string(&mut uploaded).await {
         Ok(_) => {},
+        Err(err) => {
+            tracing::error!("Failed to read uploaded checksum {}: {err}", file_path.display());
+            return Return {
+                status: actix_web::http::StatusCode::BAD_REQUEST,
+                content: Content::Str("Failed to read the uploaded checksum"),
+                content_type: actix_web::http::header::ContentType::plaintext(),
+                header_map: None,
+            }
+        }
     }
-    
-    let info = match PathInfo::parse(path.as_path()) {
-        Ok(v) => v,
-        Err(err) => return err,
-    };
-    let metadata = match info.get_merged_metadata(&repo, actix_web::http::Method::PUT).await {
-        Ok(v) => v,
-        Err(err) => return err,
+    if uploaded.len() as u64 > MAX_CHECKSUM_FILE_SIZE {
+        return GetRepoFileError::PutFileTooLarge.to_return();
+    }
+    let uploaded = uploaded.split_whitespace().next().unwrap_or_default().to_ascii_lowercase();
+
+    let stored = match tokio::fs::read_to_string(file_path).await {
+        Ok(v) => v.trim().to_ascii_lowercase(),
+        Err(err) if err.kind() == ErrorKind::NotFound => match compute_checksum(artifact_path.clone(), extension).await {
+            Ok(v) => v,
+            Err(err) => {
+                tracing::error!("Failed to compute the {extension} checksum of {}: {err}", artifact_path.display());
+                return GetRepoFileError::OpenFile.to_return();
+            }
+        },
+        Err(err) => {
+            tracing::error!("Failed to read stored checksum {}: {err}", file_path.display());
+            return GetRepoFileError::OpenFile.to_return();
+        }
     };
 
-    match create_file_dirs(&repo, &path).await {
-        Ok(()) => {},
-        Err(err) => return err,
+    if uploaded == stored {
+        created()
+    } else {
+        Return {
+            status: actix_web::http::StatusCode::BAD_REQUEST,
+            content: Content::Str("The uploaded checksum doesn't match the deployed file"),
+            content_type: actix_web::http::header::ContentType::plaintext(),
+            header_map: None,
+        }
     }
-    let file = match tokio::fs::OpenOptions::new()
-        .create_new(true)
-        .write(true)
-        .open(&path)
-        .await
-    {
+}
+
+/// Signatures can't be generated by the server, so the first upload gets stored.
+/// Like checksums, re-uploads are only compared to the stored signature, without writing anything.
+/// Returns `None`, if there is no stored signature yet.
+async fn compare_signature<D: tokio::io::AsyncRead + Unpin>(file_path: &Path, data: D) -> Option<Return> {
+    let stored = match tokio::fs::read(file_path).await {
         Ok(v) => v,
+        Err(err) if err.kind() == ErrorKind::NotFound => return None,
         Err(err) => {
-            tracing::error!("Failed to create new file dirs while deploying {}: {err}", path.display());
-            return match err.kind() {
-                ErrorKind::AlreadyExists => Return {
-                    status: actix_web::http::StatusCode::CONFLICT,
-                    content: Content::Str("File already exists"),
-                    content_type: actix_web::http::header::ContentType::plaintext(),
-                    header_map: None,
-                },
-                _ => Return {
-                    status: actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
-                    content: Content::Str("Failed creating file"),
-                    content_type: actix_web::http::header::ContentType::plaintext(),
-                    header_map: None,
-                }
-            }
+            tracing::error!("Failed to read stored signature {}: {err}", file_path.display());
+            return Some(GetRepoFileError::OpenFile.to_return());
         }
     };
-    let max_file_size = config.max_file_size.unwrap_or(crate::DEFAULT_MAX_FILE_SIZE);
-    let data = data.map_err(std::io::Error::other).into_async_read().compat();
-    match put_file(file, path.clone(), max_file_size, data).await {
+    let mut uploaded = Vec::with_capacity(stored.len());
+    match data.take(stored.len() as u64 + 1).read_to_end(&mut uploaded).await {
         Ok(_) => {},
-        Err(err) => return err,
+        Err(err) => {
+            tracing::error!("Failed to read uploaded signature {}: {err}", file_path.display());
+            return Some(Return {
+                status: actix_web::http::StatusCode::BAD_REQUEST,
+                content: Content::Str("Failed to read the uploaded signature"),
+                content_type: actix_web::http::header::ContentType::plaintext(),
+                header_map: None,
+            })
+        }
+    }
+    Some(if uploaded == stored {
+        created()
+    } else {
+        Return {
+            status: actix_web::http::StatusCode::BAD_REQUEST,
+            content: Content::Str("The uploaded signature doesn't match the stored one"),
+            content_type: actix_web::http::header::ContentType::plaintext(),
+            header_map: None,
+        }
+    })
+}
+
+/// Computes the lowercase hex checksum of the file at `path`, for one of the [`CHECKSUM_EXTENSIONS`].
+pub async fn compute_checksum(path: PathBuf, extension: &str) -> Result<String, std::io::Error> {
+    fn hash_file<D: Digest>(path: &Path) -> Result<String, std::io::Error> {
+        use std::io::Read;
+        let mut file = std::fs::File::open(path)?;
+        #[cfg(feature = "locking")]
+        file.lock_shared()?;
+        let mut hasher = D::new();
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let read = file.read(&mut buf)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buf[..read]);
+        }
+        Ok(data_encoding::HEXLOWER.encode(hasher.finalize().as_slice()))
+    }
+    let hash_file = match extension {
+        "md5" => hash_file::<md5::Md5>,
+        "sha1" => hash_file::<sha1_checked::Sha1>,
+        "sha256" => hash_file::<sha2::Sha256>,
+        "sha512" => hash_file::<sha2::Sha512>,
+        _ => return Err(std::io::Error::new(ErrorKind::InvalidInput, anyhow::anyhow!("Unknown checksum extension '{extension}'"))),
     };
+    tokio::task::spawn_blocking(move ||hash_file(&path)).await.unwrap_or_else(|err|Err(err.into()))
+}
+
+/// Replaces the (already exclusively locked) maven-metadata files returned by [`PathInfo::get_merged_metadata`],
+/// including their checksum files.
+/// The new contents are written to a temporary file, which replaces the locked file, so that a crash never leaves a truncated file behind.
+pub async fn write_metadata(metadata: Vec<MavenMetadataReturn>, limit: u64) -> Result<(), Return> {
     let mut js = JoinSet::new();
-    for (path, file, _, ser) in metadata {
-        js.spawn(put_file(file, path, max_file_size, Cursor::new(ser)));
+    for (path, locked, _, ser) in metadata {
+        js.spawn(async move {
+            let result = replace_metadata_file(path, ser, limit).await;
+            //Other tasks wait for this lock, until the file was replaced.
+            drop(locked);
+            result
+        });
     }
     while let Some(task) = js.join_next().await {
         match task {
             Ok(Ok(_)) => {},
-            Ok(Err(err)) => return err,
+            Ok(Err(err)) => return Err(err),
             Err(err) => {
-                tracing::error!("Panicked whilst updating maven-metadata for deployment of {}: {err}", path.display());
+                tracing::error!("Panicked whilst updating maven-metadata: {err}");
                 js.abort_all();
-                return Return {
+                return Err(Return {
                     status: actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
                     content: Content::Str("Panicked whilst updating maven-metadata"),
                     content_type: actix_web::http::header::ContentType::plaintext(),
                     header_map: None,
-                };
+                });
             }
         }
     }
+    Ok(())
+}
 
-    Return{
-        status: actix_web::http::StatusCode::CREATED,
-        content: Content::Str(""),
-        content_type: actix_web::http::header::ContentType::plaintext(),
-        header_map: None,
+/// Replaces a maven-metadata file and its checksum files with `contents`. The caller needs to hold the lock of the file.
+pub async fn replace_metadata_file(path: PathBuf, contents: String, limit: u64) -> Result<(), Return> {
+    let temp_path = temp_file::temp_path(&path);
+    let file = match File::create_new(&temp_path).await {
+        Ok(v) => v,
+        Err(err) => {
+            tracing::error!("Failed to create temporary file while updating maven-metadata {}: {err}", path.display());
+            return Err(GetRepoFileError::FileCreateFailed.to_return());
+        }
+    };
+    put_file(file, path, temp_path, RedeployPolicy::Overwrite, &RequestDigests::default(), limit, Cursor::new(contents)).await
+}
+
+/// Returns the path of the checksum (or other sibling) file of `file_path`, e.g. `foo.jar` -> `foo.jar.sha1`.
+pub fn sibling_path(file_path: &Path, extension: &str) -> PathBuf {
+    match file_path.extension() {
+        Some(v) => {
+            let mut v = v.to_os_string();
+            v.push(".");
+            v.push(extension);
+            file_path.with_extension(v.as_os_str())
+        },
+        None => {
+            file_path.with_extension(extension)
+        }
     }
 }
 
@@ -193,7 +535,34 @@ async fn create_file_dirs(repo: &Arc<str>, path: &Path) -> Result<(), Return> {
         }
     }
 }
-async fn put_file<D: tokio::io::AsyncRead + Unpin>(file: File, file_path: PathBuf, limit: u64, mut data: D) -> Result<Vec<PathBuf>, Return> {
+/// Writes `data` to `file` and generates the checksum files of `file_path`.
+/// `file` is the temporary file at `temp_path`, which only gets moved to `file_path` according to the [`RedeployPolicy`], once everything is written.
+/// Nothing is kept, if `data` doesn't match the `request_digests`.
+async fn put_file<D: tokio::io::AsyncRead + Unpin>(file: File, file_path: PathBuf, temp_path: PathBuf, redeploy_policy: RedeployPolicy, request_digests: &RequestDigests, limit: u64, data: D) -> Result<(), Return> {
+    let staged = stage_file(file, file_path, temp_path, request_digests, limit, data).await?;
+    let file_path = staged.file_path.clone();
+    let digests = staged.persist(redeploy_policy).await?;
+    crate::checksum_deploy::index_file(file_path, digests);
+    Ok(())
+}
+
+/// A file and its checksum files, which are completely written to their temporary locations, but not moved to their final locations yet.
+pub struct StagedFile {
+    file_path: PathBuf,
+    temp_path: PathBuf,
+    /// Temporary and final paths of the checksum files
+    hash_files: Vec<(PathBuf, PathBuf)>,
+    digests: StoredDigests,
+    /// The final paths of the file and its checksum files, with hard-links of the files they replace (`None`, if there was none).
+    /// Only kept while [`StagedFile::persist`] moves the files.
+    backups: Vec<(PathBuf, Option<PathBuf>)>,
+    /// How many of the `backups` were already replaced
+    replaced: usize,
+}
+
+/// Writes `data` to `file` (the temporary file at `temp_path`) and generates the checksum files of `file_path` next to it.
+/// Nothing is kept, if `data` doesn't match the `request_digests`.
+pub async fn stage_file<D: tokio::io::AsyncRead + Unpin>(file: File, file_path: PathBuf, temp_path: PathBuf, request_digests: &RequestDigests, limit: u64, mut data: D) -> Result<StagedFile, Return> {
     let mut files = Vec::with_capacity(1 + 4);
     let mut file = WriteFile {
         file: tokio::io::BufWriter::new(file),
@@ -201,7 +570,7 @@ async fn put_file<D: tokio::io::AsyncRead + Unpin>(file: File, file_path: PathBu
         read: 0,
         hashers: Default::default(),
     };
-    files.push(file_path.clone());
+    files.push(temp_path.clone());
     //Write to file
     match tokio::io::copy(&mut data, &mut file).await {
         Ok(_) => {},
@@ -228,21 +597,13 @@ async fn put_file<D: tokio::io::AsyncRead + Unpin>(file: File, file_path: PathBu
         }
     }
 
+    let mut hash_files = Vec::with_capacity(CHECKSUM_EXTENSIONS.len());
     macro_rules! write_file_hash {
         ($hasher:ident, $extension: literal) => {
-            let hasher = $hasher;
-            let hash_file_path = match file_path.extension() {
-                Some(v) => {
-                    let mut v = v.to_os_string();
-                    v.push(".");
-                    v.push($extension);
-                    file_path.with_extension(v.as_os_str())
-                },
-                None => {
-                    file_path.with_extension($extension)
-                }
-            }; 
-            let mut file = match tokio::fs::File::create_new(&hash_file_path).await {
+            let hash = $hasher;
+            let hash_file_path = sibling_path(&file_path, $extension);
+            let hash_temp_path = temp_file::temp_path(&hash_file_path);
+            let mut file = match tokio::fs::File::create_new(&hash_temp_path).await {
                 Ok(v) => v,
                 Err(err) => {
                     tracing::error!("Failed to create hash of file {}.{}: {err}", file_path.display(), $extension);
@@ -255,8 +616,8 @@ async fn put_file<D: tokio::io::AsyncRead + Unpin>(file: File, file_path: PathBu
                     })
                 }
             };
-            files.push(hash_file_path);
-            let hash = hasher.finalize();
+            files.push(hash_temp_path.clone());
+            hash_files.push((hash_temp_path, hash_file_path));
             let hash = data_encoding::HEXLOWER.encode(hash.as_slice());
             match file.write_all(hash.as_bytes()).await {
                 Ok(()) => {},
@@ -286,11 +647,28 @@ async fn put_file<D: tokio::io::AsyncRead + Unpin>(file: File, file_path: PathBu
             }
         };
     }
-    let (md5, sha1, sha2_256, sha2_512) = file.hashers;
+    let (md5, sha1, sha2_256, sha2_512, blake3) = file.hashers;
+    let (md5, sha1, sha2_256, sha2_512) = (md5.finalize(), sha1.finalize(), sha2_256.finalize(), sha2_512.finalize());
+    if let Some(algorithm) = request_digests.mismatch(&md5, &sha1, &sha2_256, &sha2_512, blake3.finalize().as_bytes()) {
+        tracing::warn!("Received body for {} doesn't match the {algorithm} digest sent by the client", file_path.display());
+        remove_files(&files).await;
+        return Err(Return{
+            status: actix_web::http::StatusCode::BAD_REQUEST,
+            content: Content::String(format!("The received file doesn't match the {algorithm} digest sent in the request headers")),
+            content_type: actix_web::http::header::ContentType::plaintext(),
+            header_map: None,
+        })
+    }
+    let digests = StoredDigests {
+        sha1: Some(sha1.to_vec()),
+        sha256: Some(sha2_256.to_vec()),
+        blake3: Some(blake3.finalize().as_bytes().to_vec()),
+    };
     write_file_hash!(md5, "md5");
     write_file_hash!(sha1, "sha1");
     write_file_hash!(sha2_256, "sha256");
     write_file_hash!(sha2_512, "sha512");
+
     #[cfg(feature = "locking")]
     let file = file.file.into_inner().into_std().await;
     match tokio::task::spawn_blocking(move ||{
@@ -302,6 +680,7 @@ async fn put_file<D: tokio::io::AsyncRead + Unpin>(file: File, file_path: PathBu
         Ok(Ok(())) => {}
         Ok(Err(err)) => {
             tracing::error!("Error whilst unlocking file {}: {err}", file_path.display());
+            remove_files(&files).await;
             return Err(Return {
                 status: actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
                 content: Content::Str("Error whilst unlocking file"),
@@ -311,6 +690,7 @@ async fn put_file<D: tokio::io::AsyncRead + Unpin>(file: File, file_path: PathBu
         }
         Err(err) => {
             tracing::error!("Panicked whilst unlocking file {}: {err}", file_path.display());
+            remove_files(&files).await;
             return Err(Return {
                 status: actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
                 content: Content::Str("Panicked whilst unlocking file"),
@@ -320,12 +700,120 @@ async fn put_file<D: tokio::io::AsyncRead + Unpin>(file: File, file_path: PathBu
         }
     }
 
-    Ok(files)
+    Ok(StagedFile {
+        file_path,
+        temp_path,
+        hash_files,
+        digests,
+        backups: Vec::new(),
+        replaced: 0,
+    })
+}
+
+impl StagedFile {
+    /// Moves the file to its final location according to the [`RedeployPolicy`], followed by its checksum files.
+    /// If that fails, the files, which were already replaced, are restored.
+    /// Returns the digests of the stored file.
+    pub async fn persist(mut self, redeploy_policy: RedeployPolicy) -> Result<StoredDigests, Return> {
+        //The replaced files are kept as hard-links, so that a new file never ends up next to the old checksums.
+        for file in core::iter::once(&self.file_path).chain(self.hash_files.iter().map(|(_, v)|v)) {
+            let backup = temp_file::temp_path(file);
+            match tokio::fs::hard_link(file, &backup).await {
+                Ok(()) => self.backups.push((file.clone(), Some(backup))),
+                Err(err) if err.kind() == ErrorKind::NotFound => self.backups.push((file.clone(), None)),
+                Err(err) => {
+                    tracing::error!("Failed to back up {} before replacing it: {err}", file.display());
+                    self.discard().await;
+                    return Err(GetRepoFileError::FileWriteFailed.to_return());
+                }
+            }
+        }
+        //Everything is written, so the file can now be moved to its final location.
+        //The file goes first, so that the checksums of an existing file aren't replaced, if it got deployed concurrently.
+        match temp_file::persist(&self.temp_path, &self.file_path, redeploy_policy == RedeployPolicy::Overwrite).await {
+            Ok(()) => {},
+            Err(err) if err.kind() == ErrorKind::AlreadyExists && redeploy_policy == RedeployPolicy::IdenticalOnly => {
+                let file_path = self.file_path.clone();
+                let digests = std::mem::take(&mut self.digests);
+                self.discard().await;
+                let path = file_path.clone();
+                return match tokio::task::spawn_blocking(move ||hash_file_blake3(&path)).await {
+                    //Nothing changes, so the existing file and checksums can be kept.
+                    Ok(Ok(v)) if digests.blake3.as_deref() == Some(v.as_bytes().as_slice()) => Ok(digests),
+                    Ok(Ok(_)) => Err(file_exists()),
+                    Ok(Err(err)) => {
+                        tracing::error!("Failed to hash existing file {} for redeploy: {err}", file_path.display());
+                        Err(GetRepoFileError::OpenFile.to_return())
+                    },
+                    Err(err) => {
+                        tracing::error!("Panicked whilst hashing existing file {} for redeploy: {err}", file_path.display());
+                        Err(GetRepoFileError::OpenFile.to_return())
+                    },
+                };
+            }
+            Err(err) => {
+                self.discard().await;
+                if err.kind() == ErrorKind::AlreadyExists {
+                    return Err(file_exists());
+                }
+                tracing::error!("Failed to move {} to {}: {err}", self.temp_path.display(), self.file_path.display());
+                return Err(GetRepoFileError::FileWriteFailed.to_return());
+            }
+        }
+        self.replaced += 1;
+        for (hash_temp_path, hash_file_path) in &self.hash_files {
+            match temp_file::persist(hash_temp_path, hash_file_path, true).await {
+                Ok(()) => self.replaced += 1,
+                Err(err) => {
+                    tracing::error!("Failed to move {} to {}: {err}", hash_temp_path.display(), hash_file_path.display());
+                    self.discard().await;
+                    return Err(GetRepoFileError::FileWriteFailed.to_return());
+                }
+            }
+        }
+        for backup in self.backups.iter().filter_map(|(_, v)|v.as_ref()) {
+            temp_file::remove(backup).await;
+        }
+        Ok(self.digests)
+    }
+
+    /// Removes the temporary files.
+    /// The files, which a failed [`Self::persist`] already replaced, are restored.
+    pub async fn discard(&self) {
+        temp_file::remove(&self.temp_path).await;
+        for (hash_temp_path, _) in &self.hash_files {
+            temp_file::remove(hash_temp_path).await;
+        }
+        for (i, (file, backup)) in self.backups.iter().enumerate() {
+            let result = match backup {
+                Some(backup) if i < self.replaced => temp_file::persist(backup, file, true).await,
+                Some(backup) => {
+                    temp_file::remove(backup).await;
+                    Ok(())
+                },
+                None if i < self.replaced => tokio::fs::remove_file(file).await,
+                None => Ok(()),
+            };
+            if let Err(err) = result {
+                tracing::error!("Failed to restore {}: {err}", file.display());
+            }
+        }
+    }
+}
+fn hash_file_blake3(path: &Path) -> Result<blake3::Hash, std::io::Error> {
+    let file = std::fs::File::open(path)?;
+    #[cfg(feature = "locking")]
+    file.lock_shared()?;
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(file)?;
+    Ok(hasher.finalize())
 }
 async fn remove_files(files: &Vec<PathBuf>) {
     for file in files {
         match tokio::fs::remove_file(&file).await {
             Ok(()) => {},
+            //Temporary files, which were already moved to their final location
+            Err(err) if err.kind() == ErrorKind::NotFound => {},
             Err(err) => {
                 tracing::error!("Error deleting File after error writing to File {}: {err}", file.display());
             }
@@ -337,7 +825,7 @@ struct WriteFile {
     file: tokio::io::BufWriter<tokio::fs::File>,
     limit: u64,
     read: u64,
-    hashers: (md5::Md5, sha1_checked::Sha1, sha2::Sha256, sha2::Sha512),
+    hashers: (md5::Md5, sha1_checked::Sha1, sha2::Sha256, sha2::Sha512, blake3::Hasher),
 }
 impl tokio::io::AsyncWrite for WriteFile {
     fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, Error>> {
@@ -352,11 +840,12 @@ impl tokio::io::AsyncWrite for WriteFile {
         let buf = &buf[..written];
 
         use digest::Digest;
-        let (md5, sha1, sha2_256, sha2_512) = &mut self.hashers;
+        let (md5, sha1, sha2_256, sha2_512, blake3) = &mut self.hashers;
         md5.update(buf);
         sha1.update(buf);
         sha2_256.update(buf);
         sha2_512.update(buf);
+        blake3.update(buf);
         Poll::Ready(Ok(written))
     }
 
@@ -133,7 +133,7 @@ pub async fn read_remotes<'a, T: Deref<Target = str> + Send + 'a>(
     mem: &mut memmap2::Mmap,
     file: &mut tokio::fs::File,
     hash: &blake3::Hash,
-) -> Result<(T, reqwest::Response, Option<blake3::Hash>), Vec<anyhow::Error>> {
+) -> Result<(T, reqwest::Response, Option<blake3::Hash>, bool), Vec<anyhow::Error>> {
     let file = tokio::sync::Mutex::new(file);
     let mut futures = FuturesUnordered::new();
     for (i, url) in upstreams {
@@ -173,7 +173,7 @@ pub async fn read_remotes<'a, T: Deref<Target = str> + Send + 'a>(
 
                     tracing::info!("File unchanged for {}", &*url);
                 }
-                return Ok((url, resp, new_hash))
+                return Ok((url, resp, new_hash, needs_update))
             }
         }
     }
@@ -1,5 +1,6 @@
 use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
 use serde_derive::{Deserialize, Serialize};
 use tokio::time::Instant;
@@ -21,6 +22,16 @@ pub struct Repository{
     pub time_fresh: Option<Duration>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub max_file_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redeploy_policy: Option<RedeployPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot_retention: Option<SnapshotRetention>,
+    /// Name of a [`Upstream::Local`] upstream, which receives all PUT and DELETE requests made to this repository.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deploy_target: Option<Box<str>>,
+    /// Only set on staging repositories: the hosted repository, which the staged files get promoted into.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub staging_target: Option<Box<str>>,
     #[serde(alias="cache_control", default, skip_serializing_if = "Vec::is_empty")]
     pub cache_control_file: Vec<Header>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -29,6 +40,8 @@ pub struct Repository{
     pub cache_control_dir_listings: Vec<Header>,
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub cache_control_status_code: HashMap<u16, Vec<Header>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<Webhook>,
     #[serde(default)]
     pub upstreams: Vec<Upstream>,
     #[serde(default)]
@@ -43,10 +56,15 @@ impl Default for Repository{
             infer_content_type_on_file_extension: None,
             time_fresh: None,
             max_file_size: None,
+            redeploy_policy: None,
+            snapshot_retention: None,
+            deploy_target: None,
+            staging_target: None,
             cache_control_file: Vec::new(),
             cache_control_metadata: Vec::new(),
             cache_control_dir_listings: Vec::new(),
             cache_control_status_code: Default::default(),
+            webhooks: Vec::new(),
             upstreams: Vec::new(),
             tokens: Default::default(),
         }
@@ -59,12 +77,36 @@ impl Repository {
         self.hide_directory_listings = self.hide_directory_listings.or(other.hide_directory_listings);
         self.infer_content_type_on_file_extension = self.infer_content_type_on_file_extension.or(other.infer_content_type_on_file_extension);
         self.max_file_size = self.max_file_size.or(other.max_file_size);
+        self.redeploy_policy = self.redeploy_policy.or(other.redeploy_policy);
+        self.snapshot_retention = self.snapshot_retention.clone().or(other.snapshot_retention.clone());
         self.cache_control_file.extend(other.cache_control_file.clone());
         self.cache_control_metadata.extend(other.cache_control_metadata.clone());
         self.cache_control_dir_listings.extend(other.cache_control_dir_listings.clone());
         self.cache_control_status_code.extend(other.cache_control_status_code.clone());
+        self.webhooks.extend(other.webhooks.clone());
         self.tokens.extend(other.tokens.clone());
     }
+    /// Derives the config of a staging repository from the config of the repository it gets promoted into.
+    pub fn staging_config(&self, target: &str) -> Repository {
+        let mut config = self.clone();
+        config.staging_target = Some(Box::from(target));
+        config.deploy_target = None;
+        config.upstreams = Vec::new();
+        //Files only land in the target once promoted
+        config.webhooks = Vec::new();
+        config
+    }
+    /// Returns the [`Repository::deploy_target`], if it refers to one of the local upstreams of this repository.
+    #[cfg_attr(not(feature = "put"), allow(dead_code))]
+    pub fn get_deploy_target(&self) -> Option<&str> {
+        let target = self.deploy_target.as_deref()?;
+        if self.upstreams.iter().any(|v|matches!(v, Upstream::Local(v) if &*v.path == target)) {
+            Some(target)
+        } else {
+            tracing::warn!("Ignoring deploy_target '{target}', because it isn't a local upstream of the repository");
+            None
+        }
+    }
     pub fn apply_cache_control(&self, ret: &mut Return) {
         let header_map = ret.header_map.get_or_insert_default();
         if let Some(headers) = self.cache_control_status_code.get(&ret.status.as_u16()) {
@@ -84,6 +126,45 @@ impl Repository {
     }
 }
 
+/// Decides what happens, if a release is deployed to a path, which already exists.
+/// Snapshots can always be overwritten.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub enum RedeployPolicy{
+    /// Releases can never be overwritten.
+    #[default]
+    Immutable,
+    /// Releases can be redeployed, if the content is byte-identical (e.g. retried CI jobs).
+    IdenticalOnly,
+    /// Everything can be overwritten.
+    Overwrite,
+}
+
+/// Limits how many timestamped builds are kept per snapshot version.
+/// A build is kept, if it's one of the last `keep_builds` builds or if it's younger than `max_age`.
+/// The newest build is always kept.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SnapshotRetention{
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_builds: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<Duration>,
+    /// If set, the whole repository is pruned periodically, in addition to shortly after snapshot deployments.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<Duration>,
+}
+
+/// An endpoint, which gets a JSON event POSTed to it, after files were deployed or deleted.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Webhook{
+    pub url: Box<str>,
+    /// If set, the body is signed with HMAC-SHA256 and sent as `X-Hub-Signature-256: sha256=<hex>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<Box<str>>,
+    /// Only changes below one of these paths (e.g. `com/example`) trigger the webhook. All changes do, if this is empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<Box<str>>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum Upstream{
     Local(LocalUpstream),
@@ -200,20 +281,88 @@ impl Repository {
 }
 
 
+#[cfg_attr(not(feature = "put"), allow(dead_code))]
+pub fn get_repo_config(repo: &str) -> Result<Arc<Repository>, GetRepoFileError> {
+    match get_repo(repo) {
+        Some((_, v)) => Ok(v),
+        None => Err(GetRepoFileError::NotFound),
+    }
+}
+/// Looks up a repository by name, including the staging repositories.
+pub fn get_repo(repo: &str) -> Option<(Arc<str>, Arc<Repository>)> {
+    if let Some((k, v)) = crate::REPOSITORIES.get_key_value(repo) {
+        return Some((k.clone(), v.clone()));
+    }
+    crate::STAGING_REPOSITORIES.read()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .get_key_value(repo)
+        .map(|(k, v)|(k.clone(), v.config.clone()))
+}
+
+/// A staging repository, which is available to requests until it gets promoted or dropped.
+#[derive(Clone)]
+pub struct StagingRepository {
+    pub config: Arc<Repository>,
+    /// Requests, which write to the staging repository, hold a read guard, whilst promoting or dropping it holds the write guard.
+    /// Set to true, once the staging repository is gone.
+    closed: Arc<tokio::sync::RwLock<bool>>,
+}
+/// Makes a staging repository available to requests.
+/// Staging repositories are created and dropped at runtime, so they don't live in [`crate::REPOSITORIES`].
+pub fn register_staging_repo(name: Arc<str>, config: Arc<Repository>) {
+    crate::STAGING_REPOSITORIES.write()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .insert(name, StagingRepository { config, closed: Arc::default() });
+}
+/// Waits for promotes or drops of the staging repository to finish, and keeps new ones from starting until the guard is dropped.
+/// Returns None, if the staging repository doesn't exist (anymore).
+#[cfg_attr(not(feature = "put"), allow(dead_code))]
+pub async fn lock_staging_repo(name: &str) -> Option<tokio::sync::OwnedRwLockReadGuard<bool>> {
+    let closed = get_staging_repo(name)?.closed;
+    let guard = closed.read_owned().await;
+    (!*guard).then_some(guard)
+}
+/// Waits for all requests, which write to the staging repository, to finish, and then keeps new ones from starting until the guard is dropped.
+/// Returns None, if the staging repository doesn't exist (anymore).
+/// The staging repository stays available, unless [`close_staging_repo`] is called with the guard.
+#[cfg_attr(not(feature = "put"), allow(dead_code))]
+pub async fn lock_staging_repo_exclusive(name: &str) -> Option<tokio::sync::OwnedRwLockWriteGuard<bool>> {
+    let closed = get_staging_repo(name)?.closed;
+    let guard = closed.write_owned().await;
+    (!*guard).then_some(guard)
+}
+/// Removes a staging repository from the ones available to requests.
+/// Requests, which were waiting for the guard, see the staging repository as gone.
+#[cfg_attr(not(feature = "put"), allow(dead_code))]
+pub fn close_staging_repo(name: &str, guard: &mut tokio::sync::OwnedRwLockWriteGuard<bool>) {
+    **guard = true;
+    crate::STAGING_REPOSITORIES.write()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .remove(name);
+}
+fn get_staging_repo(name: &str) -> Option<StagingRepository> {
+    crate::STAGING_REPOSITORIES.read()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .get(name)
+        .cloned()
+}
+
 pub const OUT_VEC_STACKSIZE:usize = 32;
-pub fn get_repo_look_locations(repo: &'static str, config: &'static Repository) -> (smallvec::SmallVec<[(&'static str, &'static Repository); OUT_VEC_STACKSIZE]>, Vec<GetRepoFileError>) {
+/// The repository itself and the local upstreams, which it looks into.
+pub type LookLocations = smallvec::SmallVec<[(Arc<str>, Arc<Repository>); OUT_VEC_STACKSIZE]>;
+pub fn get_repo_look_locations(repo: &Arc<str>, config: &Arc<Repository>) -> (LookLocations, Vec<GetRepoFileError>) {
     let mut start = Instant::now();
     let mut next;
 
     let mut errors = Vec::new();
-    let mut to_visit = smallvec::SmallVec::<[(&str, &Repository); OUT_VEC_STACKSIZE]>::new();
+    let mut to_visit = smallvec::SmallVec::<[(Arc<str>, Arc<Repository>); OUT_VEC_STACKSIZE]>::new();
     let mut out = smallvec::SmallVec::new();
 
-    out.push((repo, config));
+    out.push((repo.clone(), config.clone()));
 
     let mut visited = HashSet::new();
 
-    to_visit.push((repo, config));
+    to_visit.push((repo.clone(), config.clone()));
 
     while let Some((repo, config)) = to_visit.pop() {
         for upstream in &config.upstreams{
@@ -222,10 +371,10 @@ pub fn get_repo_look_locations(repo: &'static str, config: &'static Repository)
                 Upstream::Remote(_) => continue,
             };
             if visited.insert(upstream.path.clone()) {
-                match crate::REPOSITORIES.get(upstream.path.as_ref()) {
-                    Some(repo) => {
-                        out.push((&*upstream.path, repo));
-                        to_visit.push((&*upstream.path, repo));
+                match crate::REPOSITORIES.get_key_value(upstream.path.as_ref()) {
+                    Some((name, repo)) => {
+                        out.push((name.clone(), repo.clone()));
+                        to_visit.push((name.clone(), repo.clone()));
                     },
                     None => {
                         errors.push(GetRepoFileError::NotFound);
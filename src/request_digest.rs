@@ -0,0 +1,96 @@
+#![cfg(feature = "put")]
+use actix_web::http::header::HeaderMap;
+
+/// Digests of the request body, which the client sent along in headers.
+/// Supported are `Content-MD5`, `Repr-Digest` and `Content-Digest` (RFC 9530) and Artifactory's `X-Checksum-*` headers.
+/// Blake3 is only accepted through `X-Checksum-Blake3`.
+#[derive(Debug, Clone, Default)]
+pub struct RequestDigests {
+    pub md5: Option<Vec<u8>>,
+    pub sha1: Option<Vec<u8>>,
+    pub sha256: Option<Vec<u8>>,
+    pub sha512: Option<Vec<u8>>,
+    pub blake3: Option<Vec<u8>>,
+}
+impl RequestDigests {
+    /// Returns the reason, why the digest headers are rejected.
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, String> {
+        let mut out = Self::default();
+        for value in headers.get_all("Content-MD5") {
+            let digest = header_str("Content-MD5", value)
+                .and_then(|v|data_encoding::BASE64.decode(v.trim().as_bytes()).map_err(|err|malformed("Content-MD5", err)))?;
+            set_digest(&mut out.md5, digest, "Content-MD5")?;
+        }
+        for name in ["Repr-Digest", "Content-Digest"] {
+            for value in headers.get_all(name) {
+                let value = header_str(name, value)?;
+                for entry in value.split(",") {
+                    let (algorithm, digest) = match entry.split_once("=") {
+                        Some(v) => v,
+                        None => return Err(malformed(name, "expected 'algorithm=:digest:'")),
+                    };
+                    let slot = match algorithm.trim().to_ascii_lowercase().as_str() {
+                        "md5" => &mut out.md5,
+                        "sha" => &mut out.sha1,
+                        "sha-256" => &mut out.sha256,
+                        "sha-512" => &mut out.sha512,
+                        //Unknown algorithms are to be ignored
+                        _ => continue,
+                    };
+                    let digest = match digest.trim().strip_prefix(":").and_then(|v|v.strip_suffix(":")) {
+                        Some(v) => v,
+                        None => return Err(malformed(name, "expected the digest to be a byte sequence")),
+                    };
+                    let digest = data_encoding::BASE64.decode(digest.as_bytes()).map_err(|err|malformed(name, err))?;
+                    set_digest(slot, digest, name)?;
+                }
+            }
+        }
+        for (name, slot) in [
+            ("X-Checksum-Md5", &mut out.md5),
+            ("X-Checksum-Sha1", &mut out.sha1),
+            ("X-Checksum-Sha256", &mut out.sha256),
+            ("X-Checksum-Blake3", &mut out.blake3),
+        ] {
+            for value in headers.get_all(name) {
+                let digest = header_str(name, value)
+                    .and_then(|v|data_encoding::HEXLOWER_PERMISSIVE.decode(v.trim().as_bytes()).map_err(|err|malformed(name, err)))?;
+                set_digest(slot, digest, name)?;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Compares the sent digests with the ones computed from the received body.
+    /// Returns the name of the first algorithm, which doesn't match.
+    pub fn mismatch(&self, md5: &[u8], sha1: &[u8], sha256: &[u8], sha512: &[u8], blake3: &[u8]) -> Option<&'static str> {
+        [
+            ("md5", &self.md5, md5),
+            ("sha1", &self.sha1, sha1),
+            ("sha256", &self.sha256, sha256),
+            ("sha512", &self.sha512, sha512),
+            ("blake3", &self.blake3, blake3),
+        ].into_iter()
+            .find(|(_, expected, computed)|expected.as_deref().is_some_and(|v|v != *computed))
+            .map(|(name, _, _)|name)
+    }
+}
+
+fn header_str<'a>(name: &str, value: &'a actix_web::http::header::HeaderValue) -> Result<&'a str, String> {
+    value.to_str().map_err(|err|malformed(name, err))
+}
+
+/// Multiple headers might specify the same algorithm, but they need to agree.
+fn set_digest(slot: &mut Option<Vec<u8>>, digest: Vec<u8>, name: &str) -> Result<(), String> {
+    match slot {
+        Some(v) if *v != digest => Err(format!("The digest in the {name} header contradicts another digest header")),
+        _ => {
+            *slot = Some(digest);
+            Ok(())
+        }
+    }
+}
+
+fn malformed(name: &str, err: impl std::fmt::Display) -> String {
+    format!("Malformed {name} header: {err}")
+}
@@ -0,0 +1,178 @@
+#![cfg(feature = "put")]
+use std::collections::{BTreeMap, HashSet};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use crate::delete::delete_artifact_file;
+use crate::maven_metadata::{MavenMetadata, SnapshotVersion};
+use crate::repository::Repository;
+
+/// A deploy uploads several files of a build in quick succession, so pruning after a deploy waits for it to finish.
+const PRUNE_DELAY: Duration = Duration::from_secs(30);
+/// Version directories (prefixed with their repository), which have a prune scheduled by [`schedule_prune`].
+static SCHEDULED: LazyLock<Mutex<HashSet<PathBuf>>> = LazyLock::new(Default::default);
+/// Prunes run one at a time, so that they don't race each other for the same builds and maven-metadata.
+static PRUNING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Prunes the snapshot version in `version_dir` (relative to the repository) after [`PRUNE_DELAY`].
+/// Further calls for the same version before then are covered by the already scheduled prune.
+pub fn schedule_prune(repo: Arc<str>, config: Arc<Repository>, version_dir: PathBuf) {
+    let key = Path::new(&*repo).join(&version_dir);
+    if !SCHEDULED.lock().unwrap_or_else(std::sync::PoisonError::into_inner).insert(key.clone()) {
+        return;
+    }
+    tokio::spawn(async move {
+        tokio::time::sleep(PRUNE_DELAY).await;
+        SCHEDULED.lock().unwrap_or_else(std::sync::PoisonError::into_inner).remove(&key);
+        prune_snapshot_version(&repo, &config, &version_dir).await;
+    });
+}
+
+/// Starts a background task for every repository, which has a [`crate::repository::SnapshotRetention::interval`] configured.
+pub fn spawn_scheduled_pruning() {
+    for (repo, config) in crate::REPOSITORIES.iter() {
+        let interval = match config.snapshot_retention.as_ref().and_then(|v|v.interval) {
+            Some(v) => v,
+            None => continue,
+        };
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                prune_repository(repo, config).await;
+            }
+        });
+    }
+}
+
+/// Prunes all snapshot versions in the repository.
+pub async fn prune_repository(repo: &str, config: &Repository) {
+    let root = PathBuf::from(repo);
+    let version_dirs = match tokio::task::spawn_blocking(move ||find_snapshot_dirs(&root)).await {
+        Ok(Ok(v)) => v,
+        Ok(Err(err)) => {
+            tracing::error!("{repo}: Failed to search for snapshot versions to prune: {err}");
+            return;
+        }
+        Err(err) => {
+            tracing::error!("{repo}: Panicked whilst searching for snapshot versions to prune: {err}");
+            return;
+        }
+    };
+    for version_dir in version_dirs {
+        prune_snapshot_version(repo, config, &version_dir).await;
+    }
+}
+
+/// Returns all `-SNAPSHOT` directories (relative to `root`), which have a maven-metadata.xml.
+fn find_snapshot_dirs(root: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut out = Vec::new();
+    let mut to_visit = vec![PathBuf::new()];
+    while let Some(dir) = to_visit.pop() {
+        for entry in std::fs::read_dir(root.join(&dir))? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with(".") {
+                continue;
+            }
+            let path = dir.join(&*name);
+            if name.ends_with("-SNAPSHOT") && root.join(&path).join("maven-metadata.xml").is_file() {
+                out.push(path);
+            } else {
+                to_visit.push(path);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Deletes old timestamped builds of the snapshot version in `version_dir` (relative to the repository),
+/// according to the [`crate::repository::SnapshotRetention`] of the repository.
+/// The version-level maven-metadata.xml is updated through the same logic, which handles DELETE requests.
+pub async fn prune_snapshot_version(repo: &str, config: &Repository, version_dir: &Path) {
+    let retention = match &config.snapshot_retention {
+        Some(v) if v.keep_builds.is_some() || v.max_age.is_some() => v,
+        _ => return,
+    };
+    let _pruning = PRUNING.lock().await;
+    let metadata_path = Path::new(repo).join(version_dir).join("maven-metadata.xml");
+    let contents = match tokio::fs::read_to_string(&metadata_path).await {
+        Ok(v) => v,
+        Err(err) => {
+            if err.kind() != ErrorKind::NotFound {
+                tracing::warn!("Failed to read maven-metadata {}: {err}", metadata_path.display());
+            }
+            return;
+        }
+    };
+    let metadata: MavenMetadata = match quick_xml::de::from_str(&contents) {
+        Ok(v) => v,
+        Err(err) => {
+            tracing::warn!("Failed to parse maven-metadata.xml {}: {err}", metadata_path.display());
+            return;
+        }
+    };
+    let snapshot_versions = match metadata.versioning.snapshot_versions {
+        Some(v) => v.snapshot_version,
+        None => return,
+    };
+
+    //All files of one build share the same value (e.g. `1.0-20240101.120000-1`)
+    let mut builds = BTreeMap::<(u64, String), Vec<SnapshotVersion>>::new();
+    for snapshot_version in snapshot_versions {
+        let build_number = match snapshot_version.value.rsplit_once("-").map(|(_, v)|v.parse::<u64>()) {
+            Some(Ok(v)) => v,
+            _ => {
+                tracing::warn!("Not pruning snapshot version {} in {}: no valid build-number", snapshot_version.value, metadata_path.display());
+                continue;
+            }
+        };
+        builds.entry((build_number, snapshot_version.value.clone())).or_default().push(snapshot_version);
+    }
+
+    let now = chrono::Utc::now();
+    for (i, ((_, value), files)) in builds.iter().rev().enumerate() {
+        //The newest build is what the snapshot version resolves to, so it's always kept.
+        if i == 0 {
+            continue;
+        }
+        let kept_by_count = retention.keep_builds.is_some_and(|v|(i as u64) < v);
+        let kept_by_age = retention.max_age.is_some_and(|max_age|{
+            let timestamp = value.rsplit("-").nth(1).unwrap_or_default();
+            match chrono::NaiveDateTime::parse_from_str(timestamp, "%Y%m%d.%H%M%S") {
+                Ok(v) => (now - v.and_utc()).to_std().is_ok_and(|age|age < max_age),
+                Err(err) => {
+                    tracing::warn!("Snapshot version {value} in {} has an invalid timestamp '{timestamp}': {err}", metadata_path.display());
+                    false
+                }
+            }
+        });
+        if kept_by_count || kept_by_age {
+            continue;
+        }
+
+        tracing::info!("Pruning snapshot build {value} in {repo}/{}", version_dir.display());
+        for file in files {
+            let mut file_name = format!("{}-{}", metadata.artifact_id, file.value);
+            if let Some(classifier) = &file.classifier {
+                file_name.push('-');
+                file_name.push_str(classifier);
+            }
+            if let Some(extension) = &file.extension {
+                file_name.push('.');
+                file_name.push_str(extension);
+            }
+            let path = version_dir.join(file_name);
+            let file_path = Path::new(repo).join(&path);
+            if let Err(err) = delete_artifact_file(repo, config, &path, &file_path).await {
+                tracing::error!("Failed to prune {}: {} {:?}", file_path.display(), err.status, err.content);
+            }
+        }
+    }
+}
@@ -0,0 +1,194 @@
+#![cfg(feature = "put")]
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::audit::Actor;
+use crate::bundle::{collect_files, deploy_entries};
+use crate::err::GetRepoFileError;
+use crate::repository::{close_staging_repo, get_repo_config, lock_staging_repo_exclusive, register_staging_repo, Repository};
+use crate::status::{Content, Return};
+
+/// Opens a new staging repository for `repo`.
+/// Files can be deployed to the staging repository like to any hosted repository,
+/// but only become visible in `repo` once the staging repository is promoted.
+/// The staging repository is persisted as a `.name.json` config, which only records its target.
+pub async fn open_staging_repo(repo: &str, config: &Repository) -> Return {
+    if config.staging_target.is_some() {
+        return Return {
+            status: actix_web::http::StatusCode::BAD_REQUEST,
+            content: Content::Str("A staging repository can't be staged again"),
+            content_type: actix_web::http::header::ContentType::plaintext(),
+            header_map: None,
+        };
+    }
+    let base_name = format!("{repo}-staging-{}", chrono::Utc::now().format("%Y%m%d%H%M%S"));
+    let mut name = base_name.clone();
+    let mut i = 1;
+    loop {
+        if crate::repository::get_repo(&name).is_none() {
+            match tokio::fs::create_dir(&name).await {
+                Ok(()) => break,
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => {},
+                Err(err) => {
+                    tracing::error!("Failed to create the directory for staging repository {name}: {err}");
+                    return GetRepoFileError::FileCreateFailed.to_return();
+                }
+            }
+        }
+        i += 1;
+        name = format!("{base_name}-{i}");
+    }
+
+    let stored_config = Repository {
+        staging_target: Some(Box::from(repo)),
+        ..Default::default()
+    };
+    let stored_config = match serde_json::to_vec_pretty(&stored_config) {
+        Ok(v) => v,
+        Err(err) => {
+            tracing::error!("Failed to serialize the config of staging repository {name}: {err}");
+            remove_staging_dir(&name).await;
+            return GetRepoFileError::FileWriteFailed.to_return();
+        }
+    };
+    let config_path = config_path(&name);
+    let temp = crate::temp_file::temp_path(&config_path);
+    let result = match tokio::fs::write(&temp, stored_config).await {
+        Ok(()) => crate::temp_file::persist(&temp, &config_path, false).await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        tracing::error!("Failed to write the config of staging repository {name}: {err}");
+        crate::temp_file::remove(&temp).await;
+        remove_staging_dir(&name).await;
+        return GetRepoFileError::FileWriteFailed.to_return();
+    }
+
+    register_staging_repo(Arc::from(name.as_str()), Arc::new(config.staging_config(repo)));
+    tracing::info!("{repo}: Opened staging repository {name}");
+
+    let mut header_map = actix_web::http::header::HeaderMap::new();
+    if let Ok(v) = actix_web::http::header::HeaderValue::from_str(&format!("/{name}/")) {
+        header_map.insert(actix_web::http::header::LOCATION, v);
+    }
+    Return {
+        status: actix_web::http::StatusCode::CREATED,
+        content: Content::String(name),
+        content_type: actix_web::http::header::ContentType::plaintext(),
+        header_map: Some(header_map),
+    }
+}
+
+/// Moves all files of the staging repository into its target, as if they were deployed there in one bundle.
+/// Checksums and maven-metadata are regenerated and merged into the target's metadata.
+/// If any file can't be deployed, nothing changes and the staging repository stays open.
+pub async fn promote_staging_repo(staging: &str, config: &Repository, actor: &Actor) -> Return {
+    let target = match config.staging_target.as_deref() {
+        Some(v) => v,
+        None => return not_staging(),
+    };
+    let target_config = match get_repo_config(target) {
+        Ok(v) => v,
+        Err(err) => return err.to_return(),
+    };
+    //Waits for the requests, which still write to the staging repository, and keeps new ones, promotes or drops out.
+    let mut guard = match lock_staging_repo_exclusive(staging).await {
+        Some(v) => v,
+        None => return GetRepoFileError::NotFound.to_return(),
+    };
+
+    let root = PathBuf::from(staging);
+    let entries = tokio::task::spawn_blocking(move ||collect_files(&root, Path::new(""))).await;
+    let entries = match entries {
+        Ok(Ok(v)) => v,
+        Ok(Err(err)) => {
+            tracing::error!("Failed to list the files of staging repository {staging}: {err}");
+            return GetRepoFileError::OpenFile.to_return();
+        },
+        Err(err) => {
+            tracing::error!("Panicked whilst listing the files of staging repository {staging}: {err}");
+            return GetRepoFileError::Panicked.to_return();
+        }
+    };
+
+    if entries.is_empty() {
+        return Return {
+            status: actix_web::http::StatusCode::BAD_REQUEST,
+            content: Content::Str("Nothing was deployed to the staging repository"),
+            content_type: actix_web::http::header::ContentType::plaintext(),
+            header_map: None,
+        };
+    }
+
+    match deploy_entries(&Arc::from(target), &target_config, actor, &entries).await {
+        Ok(paths) => {
+            tracing::info!("{target}: Promoted staging repository {staging} with {} files", paths.len());
+            close_staging_repo(staging, &mut guard);
+            remove_staging_repo(staging).await;
+            let mut out = String::new();
+            for path in paths {
+                out.push_str(&path.to_string_lossy());
+                out.push('\n');
+            }
+            Return {
+                status: actix_web::http::StatusCode::CREATED,
+                content: Content::String(out),
+                content_type: actix_web::http::header::ContentType::plaintext(),
+                header_map: None,
+            }
+        },
+        Err(err) => err,
+    }
+}
+
+/// Deletes the staging repository and everything deployed to it.
+pub async fn drop_staging_repo(staging: &str, config: &Repository) -> Return {
+    if config.staging_target.is_none() {
+        return not_staging();
+    }
+    let mut guard = match lock_staging_repo_exclusive(staging).await {
+        Some(v) => v,
+        None => return GetRepoFileError::NotFound.to_return(),
+    };
+    close_staging_repo(staging, &mut guard);
+    remove_staging_repo(staging).await;
+    tracing::info!("Dropped staging repository {staging}");
+    Return {
+        status: actix_web::http::StatusCode::NO_CONTENT,
+        content: Content::None,
+        content_type: actix_web::http::header::ContentType::plaintext(),
+        header_map: None,
+    }
+}
+
+fn not_staging() -> Return {
+    Return {
+        status: actix_web::http::StatusCode::BAD_REQUEST,
+        content: Content::Str("The repository is not a staging repository"),
+        content_type: actix_web::http::header::ContentType::plaintext(),
+        header_map: None,
+    }
+}
+
+fn config_path(name: &str) -> PathBuf {
+    PathBuf::from(format!(".{name}.json"))
+}
+
+/// Removes the config first, so that a partially removed staging repository doesn't get loaded again.
+async fn remove_staging_repo(name: &str) {
+    let config_path = config_path(name);
+    match tokio::fs::remove_file(&config_path).await {
+        Ok(()) => {},
+        Err(err) if err.kind() == ErrorKind::NotFound => {},
+        Err(err) => tracing::error!("Failed to delete the config of staging repository {name}: {err}"),
+    }
+    remove_staging_dir(name).await;
+}
+
+async fn remove_staging_dir(name: &str) {
+    match tokio::fs::remove_dir_all(name).await {
+        Ok(()) => {},
+        Err(err) if err.kind() == ErrorKind::NotFound => {},
+        Err(err) => tracing::error!("Failed to delete the directory of staging repository {name}: {err}"),
+    }
+}
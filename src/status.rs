@@ -12,6 +12,9 @@ pub struct Return {
 pub enum Content {
     Mmap(memmap2::Mmap),
     Response(reqwest::Response),
+    Events(crate::events::EventStream),
+    /// Parts of a file, e.g. the requested byte ranges
+    Chunks(std::collections::VecDeque<actix_web::web::Bytes>),
     Str(&'static str),
     String(String),
     Empty,
@@ -39,6 +42,8 @@ impl actix_web::body::MessageBody for Content {
                 };
                 BodySize::Sized(length)
             },
+            Self::Events(_) => BodySize::Stream,
+            Self::Chunks(chunks) => BodySize::Sized(chunks.iter().map(|v|v.len() as u64).sum()),
             Self::Str(s) => BodySize::Sized(s.len() as u64),
             Self::String(s) => BodySize::Sized(s.len() as u64),
             Self::Empty => BodySize::Sized(0),
@@ -48,9 +53,17 @@ impl actix_web::body::MessageBody for Content {
 
     fn poll_next(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Option<Result<actix_web::web::Bytes, Self::Error>>> {
         use actix_web::web::Bytes;
+        //The stream lives as long as the body, instead of being taken out for a single chunk.
+        if let Self::Events(stream) = &mut *self {
+            return stream.poll_next_unpin(cx);
+        }
+        if let Self::Chunks(chunks) = &mut *self {
+            return std::task::Poll::Ready(chunks.pop_front().map(Ok));
+        }
         match core::mem::replace(&mut*self, Self::None) {
             Self::Mmap(map) => std::task::Poll::Ready(Some(Ok(Bytes::from_owner(map)))),
             Self::Response(resp) => resp.bytes_stream().poll_next_unpin(cx).map_err(::std::io::Error::other),
+            Self::Events(_) | Self::Chunks(_) => std::task::Poll::Ready(None),
             Self::Str(s) => std::task::Poll::Ready(Some(Ok(Bytes::from_static(s.as_bytes())))),
             Self::String(s) => std::task::Poll::Ready(Some(Ok(Bytes::from(s)))),
             Self::None | Self::Empty => std::task::Poll::Ready(None),
@@ -64,7 +77,9 @@ impl actix_web::body::MessageBody for Content {
         use actix_web::web::Bytes;
         match self {
             Self::Mmap(map) => Ok(Bytes::from_owner(map)),
-            Self::Response(_) => Err(self),
+            Self::Response(_) | Self::Events(_) => Err(self),
+            Self::Chunks(mut chunks) if chunks.len() <= 1 => Ok(chunks.pop_front().unwrap_or_default()),
+            Self::Chunks(_) => Err(self),
             Self::Str(s) => Ok(Bytes::from_static(s.as_bytes())),
             Self::String(s) => Ok(Bytes::from(s)),
             Self::None | Self::Empty => Ok(Bytes::new()),
@@ -108,7 +123,7 @@ impl actix_web::ResponseError for Return {
     }
     fn error_response(&self) -> actix_web::HttpResponse<actix_web::body::BoxBody> {
         let mut resp = actix_web::HttpResponse::with_body(self.status, match &self.content{
-            Content::Mmap(_) | Content::Response(_) => actix_web::body::BoxBody::new(()),
+            Content::Mmap(_) | Content::Response(_) | Content::Events(_) | Content::Chunks(_) => actix_web::body::BoxBody::new(()),
             Content::Str(s) => actix_web::body::BoxBody::new(*s),
             Content::String(s) => actix_web::body::BoxBody::new(s.clone()),
             Content::None | Content::Empty => actix_web::body::BoxBody::new(()),
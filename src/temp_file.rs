@@ -0,0 +1,97 @@
+use std::ffi::{OsStr, OsString};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Files are written to a temporary file in the directory of their final location first,
+/// and only get moved there, once they are complete.
+/// This way an interrupted write never leaves a truncated file behind, which would then be served.
+pub const TEMP_FILE_EXTENSION: &str = "part";
+
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Returns a unique temporary path next to `path`, e.g. `foo.jar` -> `.foo.jar.1234-0.part`.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let counter = TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let file_name = path.file_name().unwrap_or_default();
+    let mut name = OsString::with_capacity(file_name.len() + 32);
+    name.push(".");
+    name.push(file_name);
+    name.push(format!(".{}-{counter}.{TEMP_FILE_EXTENSION}", std::process::id()));
+    path.with_file_name(name)
+}
+
+/// Returns true, if the file-name is one generated by [`temp_path`].
+pub fn is_temp_file(file_name: &OsStr) -> bool {
+    let file_name = file_name.to_string_lossy();
+    file_name.starts_with(".") && file_name.ends_with(const_format::concatcp!(".", TEMP_FILE_EXTENSION))
+}
+
+/// Moves the finished temporary file to its final location.
+/// If `overwrite` is false, this fails with [`ErrorKind::AlreadyExists`], if `path` already exists.
+/// On error, the temporary file is left in place.
+pub async fn persist(temp_path: &Path, path: &Path, overwrite: bool) -> Result<(), std::io::Error> {
+    if overwrite {
+        return tokio::fs::rename(temp_path, path).await;
+    }
+    //A hard-link fails atomically, if the destination already exists, unlike rename.
+    tokio::fs::hard_link(temp_path, path).await?;
+    remove(temp_path).await;
+    Ok(())
+}
+
+/// Removes a temporary file, logging any error.
+pub async fn remove(temp_path: &Path) {
+    match tokio::fs::remove_file(temp_path).await {
+        Ok(()) => {},
+        Err(err) if err.kind() == ErrorKind::NotFound => {},
+        Err(err) => {
+            tracing::error!("Error deleting temporary File {}: {err}", temp_path.display());
+        }
+    }
+}
+
+/// Recursively removes temporary files left behind by interrupted writes (e.g. a crash or power-loss).
+/// This is meant to be called on startup, before any requests are served.
+/// Only failing to read `dir` itself is an error. Entries, which can't be read or removed, are logged and skipped.
+pub fn cleanup(dir: &Path) -> Result<u64, std::io::Error> {
+    let mut removed = 0;
+    let mut to_visit = vec![dir.to_path_buf()];
+    while let Some(current) = to_visit.pop() {
+        let read_dir = match std::fs::read_dir(&current) {
+            Ok(v) => v,
+            Err(err) if current == dir => return Err(err),
+            Err(err) => {
+                tracing::error!("Failed to read directory {} while removing temporary files: {err}", current.display());
+                continue;
+            }
+        };
+        for entry in read_dir {
+            let entry = match entry {
+                Ok(v) => v,
+                Err(err) => {
+                    tracing::error!("Failed to read an entry of {} while removing temporary files: {err}", current.display());
+                    continue;
+                }
+            };
+            let path = entry.path();
+            let file_type = match entry.file_type() {
+                Ok(v) => v,
+                Err(err) => {
+                    tracing::error!("Failed to get the file-type of {} while removing temporary files: {err}", path.display());
+                    continue;
+                }
+            };
+            if file_type.is_dir() {
+                to_visit.push(path);
+            } else if file_type.is_file() && is_temp_file(&entry.file_name()) {
+                tracing::info!("Removing leftover temporary File {}", path.display());
+                match std::fs::remove_file(&path) {
+                    Ok(()) => removed += 1,
+                    Err(err) => tracing::error!("Failed to remove leftover temporary File {}: {err}", path.display()),
+                }
+            }
+        }
+    }
+    Ok(removed)
+}
@@ -0,0 +1,81 @@
+#![cfg(feature = "put")]
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::audit::{Actor, FileSummary};
+use crate::bundle::{collect_files, deploy_entries};
+use crate::err::GetRepoFileError;
+use crate::repository::Repository;
+use crate::status::{Content, Return};
+use crate::webhook::EventKind;
+
+/// Copies the file, version or artifact directory at `path` from `repo` into the hosted repository `target`.
+/// The files go through the same checks and maven-metadata updates as a deploy to `target`, and either all or none of them get copied.
+/// If `remove_source` is set, the copied path is deleted from `repo` afterwards, which also updates its maven-metadata.
+pub async fn transfer(repo: &str, config: &Repository, actor: &Actor, path: PathBuf, target: &str, target_config: &Arc<Repository>, remove_source: bool) -> Return {
+    if path.as_os_str().is_empty() {
+        return Return {
+            status: actix_web::http::StatusCode::BAD_REQUEST,
+            content: Content::Str("Refusing to copy or move the root of a repository"),
+            content_type: actix_web::http::header::ContentType::plaintext(),
+            header_map: None,
+        };
+    }
+    let entries = {
+        let repo_dir = PathBuf::from(repo);
+        let path = path.clone();
+        tokio::task::spawn_blocking(move ||collect_files(&repo_dir, &path)).await
+    };
+    let entries = match entries {
+        Ok(Ok(v)) => v,
+        Ok(Err(err)) if err.kind() == std::io::ErrorKind::NotFound => return GetRepoFileError::NotFound.to_return(),
+        Ok(Err(err)) => {
+            tracing::error!("{repo}: Failed to list the files of {}: {err}", path.display());
+            return GetRepoFileError::ReadDirectory.to_return();
+        },
+        Err(err) => {
+            tracing::error!("{repo}: Panicked whilst listing the files of {}: {err}", path.display());
+            return GetRepoFileError::Panicked.to_return();
+        }
+    };
+    if entries.is_empty() {
+        return GetRepoFileError::NotFound.to_return();
+    }
+
+    let paths = match deploy_entries(&Arc::from(target), target_config, actor, &entries).await {
+        Ok(v) => v,
+        Err(err) => return err,
+    };
+    tracing::info!("{repo}: Copied {} files of {} to {target}", paths.len(), path.display());
+
+    if remove_source {
+        let summary = FileSummary::of(&Path::new(repo).join(&path)).await;
+        let changed = crate::webhook::files_to_delete(repo, config, &path).await;
+        let ret = crate::delete::delete_path(repo, config, &path).await;
+        crate::audit::record(repo, actor, "delete", &path, &summary, ret.status).await;
+        if ret.status.is_success() {
+            crate::events::publish(repo, crate::events::ChangeKind::Deleted, &path);
+            crate::webhook::notify(repo, config, EventKind::Delete, &changed);
+        }
+        if !ret.status.is_success() {
+            tracing::error!("{repo}: Copied {} to {target}, but failed to delete it afterwards: {} {:?}", path.display(), ret.status, ret.content);
+            return Return {
+                status: ret.status,
+                content: Content::String(format!("Copied {} to {target}, but failed to delete it from {repo}", path.display())),
+                content_type: actix_web::http::header::ContentType::plaintext(),
+                header_map: ret.header_map,
+            };
+        }
+    }
+
+    let mut out = String::new();
+    for path in paths {
+        out.push_str(&path.to_string_lossy());
+        out.push('\n');
+    }
+    Return {
+        status: actix_web::http::StatusCode::CREATED,
+        content: Content::String(out),
+        content_type: actix_web::http::header::ContentType::plaintext(),
+        header_map: None,
+    }
+}
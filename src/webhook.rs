@@ -0,0 +1,203 @@
+#![cfg(feature = "put")]
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use hmac::{Hmac, Mac};
+use crate::audit::FileSummary;
+use crate::repository::{Repository, Webhook};
+
+/// Directory next to the repository configs, which holds one `<repo>.jsonl` log of webhook deliveries per repository.
+const DELIVERY_LOG_DIR: &str = ".webhooks";
+const MAX_ATTEMPTS: u32 = 5;
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+static EVENT_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Copy, serde_derive::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Deploy,
+    Delete,
+}
+impl EventKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Deploy => "deploy",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+/// A deployed or deleted file, relative to the repository.
+#[derive(Debug, Clone)]
+pub struct ChangedFile {
+    pub path: PathBuf,
+    pub summary: FileSummary,
+}
+
+#[derive(Debug, serde_derive::Serialize)]
+struct Event<'a> {
+    id: &'a str,
+    event: EventKind,
+    repository: &'a str,
+    timestamp: chrono::DateTime<chrono::Utc>,
+    group_id: Option<String>,
+    artifact_id: Option<&'a str>,
+    version: Option<&'a str>,
+    files: Vec<EventFile<'a>>,
+}
+#[derive(Debug, serde_derive::Serialize)]
+struct EventFile<'a> {
+    path: &'a str,
+    size: Option<u64>,
+    sha1: Option<&'a str>,
+    sha256: Option<&'a str>,
+}
+
+#[derive(Debug, serde_derive::Serialize)]
+struct DeliveryRecord<'a> {
+    timestamp: chrono::DateTime<chrono::Utc>,
+    id: &'a str,
+    url: &'a str,
+    event: EventKind,
+    attempt: u32,
+    status: Option<u16>,
+    error: Option<String>,
+    success: bool,
+}
+
+/// Lists the files, which a delete of `path` is about to remove, so that they can be sent to the webhooks afterwards.
+/// Returns nothing, if the repository has no webhooks.
+pub async fn files_to_delete(repo: &str, config: &Repository, path: &Path) -> Vec<ChangedFile> {
+    if config.webhooks.is_empty() {
+        return Vec::new();
+    }
+    let entries = {
+        let repo_dir = PathBuf::from(repo);
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move ||crate::bundle::collect_files(&repo_dir, &path)).await
+    };
+    let entries = match entries {
+        Ok(Ok(v)) => v,
+        Ok(Err(err)) => {
+            tracing::warn!("{repo}: Failed to list the files of {} for webhooks: {err}", path.display());
+            return Vec::new();
+        },
+        Err(err) => {
+            tracing::error!("{repo}: Panicked whilst listing the files of {} for webhooks: {err}", path.display());
+            return Vec::new();
+        }
+    };
+    let mut out = Vec::with_capacity(entries.len());
+    for entry in entries {
+        out.push(ChangedFile {
+            summary: FileSummary::of(&entry.source).await,
+            path: entry.path,
+        });
+    }
+    out
+}
+
+/// Sends events about the changed files to the webhooks of the repository in the background.
+/// Files get grouped by their version directory, so that there is one event per GAV.
+pub fn notify(repo: &str, config: &Repository, kind: EventKind, files: &[ChangedFile]) {
+    if config.webhooks.is_empty() || files.is_empty() {
+        return;
+    }
+    let mut by_version = BTreeMap::<&Path, Vec<&ChangedFile>>::new();
+    for file in files {
+        by_version.entry(file.path.parent().unwrap_or(Path::new(""))).or_default().push(file);
+    }
+
+    for webhook in &config.webhooks {
+        for (version_dir, files) in &by_version {
+            let files = files.iter()
+                //Prefixes match whole path components, so `com/example` doesn't match `com/example2`.
+                .filter(|v|webhook.paths.is_empty() || webhook.paths.iter().any(|prefix|v.path.starts_with(prefix.trim_start_matches("/"))))
+                .copied()
+                .collect::<Vec<_>>();
+            if files.is_empty() {
+                continue;
+            }
+            let id = format!("{}-{}-{}", chrono::Utc::now().timestamp_micros(), std::process::id(), EVENT_COUNTER.fetch_add(1, Ordering::Relaxed));
+            let body = match serde_json::to_vec(&event(repo, &id, kind, version_dir, &files)) {
+                Ok(v) => v,
+                Err(err) => {
+                    tracing::error!("{repo}: Failed to serialize webhook event for {}: {err}", version_dir.display());
+                    continue;
+                }
+            };
+            tokio::spawn(deliver(Box::from(repo), webhook.clone(), id, kind, body));
+        }
+    }
+}
+
+fn event<'a>(repo: &'a str, id: &'a str, kind: EventKind, version_dir: &'a Path, files: &[&'a ChangedFile]) -> Event<'a> {
+    let mut components = version_dir.iter().rev().filter_map(|v|v.to_str());
+    let version = components.next();
+    let artifact_id = components.next();
+    let mut group = components.collect::<Vec<_>>();
+    group.reverse();
+    let gav = !group.is_empty();
+    Event {
+        id,
+        event: kind,
+        repository: repo,
+        timestamp: chrono::Utc::now(),
+        group_id: gav.then(||group.join(".")),
+        artifact_id: artifact_id.filter(|_|gav),
+        version: version.filter(|_|gav),
+        files: files.iter().map(|v|EventFile {
+            path: v.path.to_str().unwrap_or_default(),
+            size: v.summary.size,
+            sha1: v.summary.sha1.as_deref(),
+            sha256: v.summary.sha256.as_deref(),
+        }).collect(),
+    }
+}
+
+/// POSTs the event, retrying with an exponential backoff.
+/// Every attempt gets recorded in the delivery log.
+async fn deliver(repo: Box<str>, webhook: Webhook, id: String, kind: EventKind, body: Vec<u8>) {
+    let signature = webhook.secret.as_ref()
+        .map(|secret|{
+            let mut mac = Hmac::<sha2::Sha256>::new_from_slice(secret.as_bytes())
+                .expect("HMAC to accept keys of any length");
+            mac.update(&body);
+            format!("sha256={}", data_encoding::HEXLOWER.encode(&mac.finalize().into_bytes()))
+        });
+    for attempt in 1..=MAX_ATTEMPTS {
+        let mut request = crate::CLIENT.post(&*webhook.url)
+            .timeout(TIMEOUT)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Maven-Repo-Event", kind.as_str())
+            .header("X-Maven-Repo-Delivery", id.as_str());
+        if let Some(signature) = &signature {
+            request = request.header("X-Hub-Signature-256", signature.as_str());
+        }
+        let (status, error) = match request.body(body.clone()).send().await {
+            Ok(v) => (Some(v.status()), None),
+            Err(err) => (err.status(), Some(err.to_string())),
+        };
+        let success = status.is_some_and(|v|v.is_success());
+        let record = DeliveryRecord {
+            timestamp: chrono::Utc::now(),
+            id: &id,
+            url: &webhook.url,
+            event: kind,
+            attempt,
+            status: status.map(|v|v.as_u16()),
+            error,
+            success,
+        };
+        crate::audit::append_record(DELIVERY_LOG_DIR, &repo, &record).await;
+        if success {
+            return;
+        }
+        if attempt < MAX_ATTEMPTS {
+            tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+        }
+    }
+    tracing::warn!("{repo}: Giving up delivering webhook event {id} to {} after {MAX_ATTEMPTS} attempts", webhook.url);
+}